@@ -0,0 +1,170 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::range_set::RangeSet;
+
+/// Conjunto de grafemas: los formados por un único carácter se guardan como rangos en un
+/// [`RangeSet`] y los compuestos por varios (banderas, secuencias ZWJ, letras con marcas
+/// combinantes) como cadenas. Así un alfabeto de millones de caracteres sigue ocupando poco
+/// y un emoji compuesto es un único elemento.
+///
+/// Los elementos se ordenan primero los caracteres sueltos, en orden de código, y después los
+/// grafemas compuestos, en orden lexicográfico. `nth` y `rank` usan ese orden.
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct GraphemeSet {
+    chars: RangeSet,
+    /// Grafemas de más de un carácter. Nunca contiene cadenas de un solo carácter, que van en
+    /// `chars`, para que cada elemento tenga una única representación.
+    clusters: BTreeSet<String>,
+}
+
+impl GraphemeSet {
+    /// Conjunto vacío.
+    pub fn new() -> Self {
+        GraphemeSet::default()
+    }
+
+    /// Añade el grafema `element`.
+    pub fn insert(&mut self, element: &str) {
+        let mut chars = element.chars();
+        match (chars.next(), chars.next()) {
+            (None, _) => {}
+            (Some(c), None) => self.chars = self.chars.union(&RangeSet::from(c)),
+            _ => {
+                self.clusters.insert(element.to_string());
+            }
+        }
+    }
+
+    /// Número de grafemas del conjunto.
+    pub fn len(&self) -> usize {
+        self.chars.len() + self.clusters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chars.is_empty() && self.clusters.is_empty()
+    }
+
+    /// Indica si `element` pertenece al conjunto.
+    pub fn contains(&self, element: &str) -> bool {
+        let mut chars = element.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => self.chars.contains(c),
+            _ => self.clusters.contains(element),
+        }
+    }
+
+    /// Grafema número `n` del conjunto, o `None` si `n >= len()`.
+    pub fn nth(&self, n: usize) -> Option<String> {
+        match self.chars.nth(n) {
+            Some(c) => Some(c.to_string()),
+            None => self.clusters.iter().nth(n - self.chars.len()).cloned(),
+        }
+    }
+
+    /// Posición de `element` dentro del conjunto (inversa de `nth`), o `None` si no pertenece.
+    pub fn rank(&self, element: &str) -> Option<usize> {
+        let mut chars = element.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => self.chars.rank(c),
+            _ if self.clusters.contains(element) => {
+                let before = self
+                    .clusters
+                    .iter()
+                    .take_while(|cluster| cluster.as_str() < element)
+                    .count();
+                Some(self.chars.len() + before)
+            }
+            _ => None,
+        }
+    }
+
+    /// Primer grafema del conjunto.
+    pub fn first(&self) -> Option<String> {
+        self.nth(0)
+    }
+
+    /// Grafemas del conjunto en orden.
+    pub fn iter(&self) -> impl Iterator<Item = String> + '_ {
+        self.chars.iter().map(String::from).chain(self.clusters.iter().cloned())
+    }
+
+    /// Grafemas de un solo carácter.
+    pub fn chars(&self) -> &RangeSet {
+        &self.chars
+    }
+
+    /// Grafemas de más de un carácter.
+    pub fn clusters(&self) -> impl Iterator<Item = &str> + '_ {
+        self.clusters.iter().map(String::as_str)
+    }
+
+    pub fn union(&self, other: &GraphemeSet) -> GraphemeSet {
+        GraphemeSet {
+            chars: self.chars.union(&other.chars),
+            clusters: self.clusters.union(&other.clusters).cloned().collect(),
+        }
+    }
+
+    pub fn difference(&self, other: &GraphemeSet) -> GraphemeSet {
+        GraphemeSet {
+            chars: self.chars.difference(&other.chars),
+            clusters: self.clusters.difference(&other.clusters).cloned().collect(),
+        }
+    }
+
+    pub fn intersection(&self, other: &GraphemeSet) -> GraphemeSet {
+        GraphemeSet {
+            chars: self.chars.intersection(&other.chars),
+            clusters: self.clusters.intersection(&other.clusters).cloned().collect(),
+        }
+    }
+}
+
+impl From<RangeSet> for GraphemeSet {
+    fn from(chars: RangeSet) -> Self {
+        GraphemeSet {
+            chars,
+            clusters: BTreeSet::new(),
+        }
+    }
+}
+
+impl From<&str> for GraphemeSet {
+    fn from(element: &str) -> Self {
+        let mut set = GraphemeSet::new();
+        set.insert(element);
+        set
+    }
+}
+
+impl<'a> FromIterator<&'a str> for GraphemeSet {
+    fn from_iter<I: IntoIterator<Item = &'a str>>(elements: I) -> Self {
+        let mut clusters = BTreeSet::new();
+        let mut chars = Vec::new();
+        for element in elements {
+            let mut iter = element.chars();
+            match (iter.next(), iter.next()) {
+                (None, _) => {}
+                (Some(c), None) => chars.push(c),
+                _ => {
+                    clusters.insert(element.to_string());
+                }
+            }
+        }
+
+        GraphemeSet {
+            chars: chars.into_iter().collect(),
+            clusters,
+        }
+    }
+}
+
+impl fmt::Debug for GraphemeSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GraphemeSet")
+            .field("chars", &self.chars)
+            .field("clusters", &self.clusters)
+            .finish()
+    }
+}
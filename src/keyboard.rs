@@ -0,0 +1,183 @@
+use crate::range_set::RangeSet;
+
+/// Distribución de teclado: qué caracteres se escriben con cada tecla y cuáles necesitan AltGr
+/// o teclas muertas. Los datos corresponden a las distribuciones estándar de Windows.
+#[derive(Debug)]
+pub struct Layout {
+    /// Código con el que se referencia en `rules.keyboard` (`"es"`, `"latam"`...).
+    pub code: &'static str,
+    pub name: &'static str,
+    /// Filas de teclas (números, superior, central e inferior) sin modificadores. Cada posición
+    /// es una tecla física, alineada entre distribuciones: la fila inferior empieza con la tecla
+    /// extra de los teclados ISO (`<` en ES o DE). Un espacio indica que la tecla no existe o no
+    /// produce un carácter en ese nivel.
+    rows: [&'static str; 4],
+    /// Las mismas filas con Mayús.
+    shifted: [&'static str; 4],
+    /// Caracteres que se escriben con AltGr (o AltGr y Mayús).
+    altgr: &'static str,
+    /// Caracteres que solo se escriben con teclas muertas: las letras compuestas (`á` con `´` y
+    /// `a`) y los propios signos de las teclas muertas, que se escriben seguidos de un espacio.
+    /// Si un signo de tecla muerta se escribe sin ella con AltGr, aparece en `altgr`.
+    dead_keys: &'static str,
+    /// Caracteres de `dead_keys` cuya tecla muerta necesita AltGr.
+    altgr_dead_keys: &'static str,
+}
+
+/// Desplazamiento horizontal de cada fila respecto a la de números, en cuartos de tecla: las
+/// filas de un teclado físico están escalonadas (Tab mide 1,5 teclas, Bloq Mayús 1,75 y Mayús
+/// izquierda, en los teclados ISO, 1,25).
+const ROW_OFFSETS: [usize; 4] = [0, 6, 7, 5];
+
+/// Dirección de un paso entre dos teclas vecinas.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Direction {
+    Left,
+    Right,
+    /// A una de las dos teclas que se tocan en la fila de arriba.
+    Up,
+    /// A una de las dos teclas que se tocan en la fila de abajo.
+    Down,
+}
+
+/// Distribuciones conocidas.
+pub const LAYOUTS: &[Layout] = &[
+    Layout {
+        code: "us",
+        name: "English (US)",
+        rows: ["`1234567890-=", "qwertyuiop[]\\", "asdfghjkl;' ", " zxcvbnm,./"],
+        shifted: ["~!@#$%^&*()_+", "QWERTYUIOP{}|", "ASDFGHJKL:\" ", " ZXCVBNM<>?"],
+        altgr: "",
+        dead_keys: "",
+        altgr_dead_keys: "",
+    },
+    Layout {
+        code: "uk",
+        name: "English (UK)",
+        rows: ["`1234567890-=", "qwertyuiop[] ", "asdfghjkl;'#", "\\zxcvbnm,./"],
+        shifted: ["¬!\"£$%^&*()_+", "QWERTYUIOP{} ", "ASDFGHJKL:@~", "|ZXCVBNM<>?"],
+        altgr: "¦€áéíóúÁÉÍÓÚ",
+        dead_keys: "",
+        altgr_dead_keys: "",
+    },
+    Layout {
+        code: "es",
+        name: "Español (España)",
+        rows: ["º1234567890'¡", "qwertyuiop +", "asdfghjklñ ç", "<zxcvbnm,.-"],
+        shifted: ["ª!\"·$%&/()=?¿", "QWERTYUIOP *", "ASDFGHJKLÑ Ç", ">ZXCVBNM;:_"],
+        altgr: "\\|@#€¬[]{}",
+        dead_keys: "`^´¨~àèìòùÀÈÌÒÙâêîôûÂÊÎÔÛáéíóúýÁÉÍÓÚÝäëïöüÿÄËÏÖÜãõÃÕ",
+        altgr_dead_keys: "~ãõÃÕ",
+    },
+    Layout {
+        code: "de",
+        name: "Deutsch",
+        rows: [" 1234567890ß ", "qwertzuiopü+", "asdfghjklöä#", "<yxcvbnm,.-"],
+        shifted: ["°!\"§$%&/()=? ", "QWERTZUIOPÜ*", "ASDFGHJKLÖÄ'", ">YXCVBNM;:_"],
+        altgr: "²³{[]}\\@€~|µ",
+        dead_keys: "^´`âêîôûÂÊÎÔÛáéíóúýÁÉÍÓÚÝàèìòùÀÈÌÒÙ",
+        altgr_dead_keys: "",
+    },
+    Layout {
+        code: "fr",
+        name: "Français (AZERTY)",
+        rows: ["²&é\"'(-è_çà)=", "azertyuiop $", "qsdfghjklmù*", "<wxcvbn,;:!"],
+        shifted: [" 1234567890°+", "AZERTYUIOP £", "QSDFGHJKLM%µ", ">WXCVBN?./§"],
+        altgr: "#{[|\\^@]}€¤",
+        dead_keys: "^¨~`âêîôûÂÊÎÔÛäëïöüÿÄËÏÖÜãñõÃÑÕìòÀÈÌÒÙ",
+        altgr_dead_keys: "~`ãñõÃÑÕìòÀÈÌÒÙ",
+    },
+    Layout {
+        code: "latam",
+        name: "Español (Latinoamérica)",
+        rows: ["|1234567890'¿", "qwertyuiop +", "asdfghjklñ{}", "<zxcvbnm,.-"],
+        shifted: ["°!\"#$%&/()=?¡", "QWERTYUIOP *", "ASDFGHJKLÑ[]", ">ZXCVBNM;:_"],
+        altgr: "¬\\@~",
+        dead_keys: "´¨^`áéíóúýÁÉÍÓÚÝäëïöüÿÄËÏÖÜâêîôûÂÊÎÔÛàèìòùÀÈÌÒÙ",
+        altgr_dead_keys: "^`âêîôûÂÊÎÔÛàèìòùÀÈÌÒÙ",
+    },
+];
+
+/// Distribución con código `code`, o `None` si no existe.
+pub fn layout(code: &str) -> Option<&'static Layout> {
+    LAYOUTS.iter().find(|layout| layout.code == code)
+}
+
+impl Layout {
+    /// Caracteres que se pueden escribir con esta distribución, incluido el espacio. Con
+    /// `altgr = false` se excluyen los que necesitan AltGr, y con `dead_keys = false` los que
+    /// necesitan una tecla muerta.
+    pub fn typeable(&self, altgr: bool, dead_keys: bool) -> RangeSet {
+        let mut chars: RangeSet = self
+            .rows
+            .iter()
+            .chain(&self.shifted)
+            .flat_map(|row| row.chars())
+            .chain([' '])
+            .collect();
+
+        if altgr {
+            chars = chars.union(&self.altgr.chars().collect());
+        }
+        if dead_keys {
+            let mut dead: RangeSet = self.dead_keys.chars().collect();
+            if !altgr {
+                dead = dead.difference(&self.altgr_dead_keys.chars().collect());
+            }
+            chars = chars.union(&dead);
+        }
+
+        chars
+    }
+
+    /// Fila y posición de la tecla con la que se escribe `c` sin AltGr, con o sin Mayús.
+    pub(crate) fn key(&self, c: char) -> Option<(usize, usize)> {
+        if c == ' ' {
+            return None;
+        }
+
+        self.rows
+            .iter()
+            .zip(&self.shifted)
+            .enumerate()
+            .find_map(|(row, (plain, shifted))| {
+                let column = plain.chars().position(|key| key == c);
+                column
+                    .or_else(|| shifted.chars().position(|key| key == c))
+                    .map(|column| (row, column))
+            })
+    }
+
+    /// Dirección del paso de `from` a `to` si sus teclas son vecinas (en la misma fila, o en
+    /// filas contiguas y solapadas), o `None` si no lo son. Una serie de pasos en la misma
+    /// dirección es un recorrido de teclado como `qwer`, `asdf` o `1qaz`.
+    pub fn walk(&self, from: char, to: char) -> Option<Direction> {
+        let (from_row, from_column) = self.key(from)?;
+        let (to_row, to_column) = self.key(to)?;
+        let x = |row: usize, column: usize| (ROW_OFFSETS[row] + 4 * column) as isize;
+        let touching = (x(from_row, from_column) - x(to_row, to_column)).abs() < 4;
+
+        if from_row == to_row {
+            match to_column.checked_sub(from_column) {
+                Some(1) => Some(Direction::Right),
+                _ if from_column.checked_sub(to_column) == Some(1) => Some(Direction::Left),
+                _ => None,
+            }
+        } else if to_row == from_row + 1 && touching {
+            Some(Direction::Down)
+        } else if from_row == to_row + 1 && touching {
+            Some(Direction::Up)
+        } else {
+            None
+        }
+    }
+
+    /// Caracteres de las teclas de la distribución, sin AltGr ni teclas muertas.
+    pub fn keys(&self) -> impl Iterator<Item = char> + '_ {
+        self.rows
+            .iter()
+            .chain(&self.shifted)
+            .flat_map(|row| row.chars())
+            .filter(|&c| c != ' ')
+    }
+}
@@ -1,3 +1,6 @@
 pub mod getpass;
+pub mod grapheme_set;
+pub mod keyboard;
 pub mod password_generator;
-mod utils;
+pub mod range_set;
+pub mod utils;
@@ -0,0 +1,291 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use super::config::Unit;
+use super::error::{PasswordGenError, Result};
+use super::pattern::{Node, Pattern, Quantifier};
+use crate::grapheme_set::GraphemeSet;
+
+/// Número máximo de estados de cada autómata. Las repeticiones copian su fragmento, así que
+/// unas pocas repeticiones anidadas (`(((d){0,30}){0,30}){0,30}`) bastan para agotar la memoria.
+const MAX_STATES: usize = 10_000;
+
+/// Error de un patrón cuyo autómata superaría `MAX_STATES`.
+fn too_complex() -> PasswordGenError {
+    PasswordGenError::InvalidConfig(format!(
+        "The pattern is too complex: it needs more than {} automaton states. Use fewer nested repetitions or smaller counts",
+        MAX_STATES
+    ))
+}
+
+/// Autómata finito no determinista que reconoce las contraseñas descritas por un patrón.
+/// Cada transición consume un elemento (carácter o grafema) de una de las clases de `classes`.
+pub(crate) struct Nfa {
+    pub classes: Vec<GraphemeSet>,
+    states: Vec<NfaState>,
+    start: usize,
+    accept: usize,
+}
+
+#[derive(Default)]
+struct NfaState {
+    epsilon: Vec<usize>,
+    edges: Vec<(usize, usize)>,
+}
+
+/// Autómata determinista sobre átomos: `transitions[estado][átomo]` es el estado siguiente al
+/// consumir un carácter del átomo. Como cada cadena sigue un único camino, contar caminos
+/// equivale a contar contraseñas distintas.
+#[derive(Clone)]
+pub(crate) struct Dfa {
+    pub transitions: Vec<Vec<Option<usize>>>,
+    pub accepting: Vec<bool>,
+}
+
+impl Nfa {
+    /// Autómata equivalente al patrón `*`: cualquier cadena del alfabeto.
+    pub fn any(alphabet: &GraphemeSet) -> Self {
+        let mut nfa = Nfa::empty();
+        let class = nfa.class(alphabet.clone());
+        nfa.states[nfa.start].edges.push((class, nfa.start));
+        nfa.accept = nfa.start;
+        nfa
+    }
+
+    /// Construye el autómata de `pattern`. `resolve` devuelve los caracteres (ya limitados al
+    /// alfabeto final) del charset de cada bloque, y `alphabet` es el alfabeto que usan el comodín
+    /// y los bloques negados. Los literales aportan sus propios caracteres aunque no estén en el
+    /// alfabeto y se dividen en elementos según `unit`. Las repeticiones se limitan a `max_length`.
+    pub fn from_pattern<F>(
+        pattern: &Pattern,
+        alphabet: &GraphemeSet,
+        unit: Unit,
+        max_length: usize,
+        mut resolve: F,
+    ) -> Result<Self>
+    where
+        F: FnMut(&str) -> Result<GraphemeSet>,
+    {
+        let mut nfa = Nfa::empty();
+        let mut builder = Builder {
+            alphabet,
+            unit,
+            max_length,
+            resolve: &mut resolve,
+        };
+
+        let start = nfa.start;
+        nfa.accept = builder.alternatives(&mut nfa, start, &pattern.alternatives)?;
+        Ok(nfa)
+    }
+
+    fn empty() -> Self {
+        Nfa {
+            classes: Vec::new(),
+            states: vec![NfaState::default()],
+            start: 0,
+            accept: 0,
+        }
+    }
+
+    fn class(&mut self, chars: GraphemeSet) -> usize {
+        self.classes.push(chars);
+        self.classes.len() - 1
+    }
+
+    fn state(&mut self) -> usize {
+        self.states.push(NfaState::default());
+        self.states.len() - 1
+    }
+
+    /// Une `from` con un estado nuevo consumiendo un carácter de `class`.
+    fn edge(&mut self, from: usize, class: usize) -> usize {
+        let next = self.state();
+        self.states[from].edges.push((class, next));
+        next
+    }
+
+    /// Determiniza el autómata. `membership[átomo][clase]` indica si el átomo pertenece a la clase.
+    /// Falla si el autómata determinista necesita más de `MAX_STATES` estados.
+    pub fn determinize(&self, membership: &[Vec<bool>]) -> Result<Dfa> {
+        let mut index: BTreeMap<BTreeSet<usize>, usize> = BTreeMap::new();
+        let mut sets = Vec::new();
+        let mut queue = VecDeque::new();
+
+        let start = self.closure([self.start].into());
+        index.insert(start.clone(), 0);
+        sets.push(start.clone());
+        queue.push_back(start);
+
+        let mut transitions = Vec::new();
+        while let Some(set) = queue.pop_front() {
+            let mut row = Vec::with_capacity(membership.len());
+
+            for classes in membership {
+                let targets: BTreeSet<usize> = set
+                    .iter()
+                    .flat_map(|&s| &self.states[s].edges)
+                    .filter(|&&(class, _)| classes[class])
+                    .map(|&(_, target)| target)
+                    .collect();
+
+                if targets.is_empty() {
+                    row.push(None);
+                    continue;
+                }
+
+                let next = self.closure(targets);
+                let id = match index.get(&next) {
+                    Some(&id) => id,
+                    None if sets.len() == MAX_STATES => return Err(too_complex()),
+                    None => {
+                        let id = sets.len();
+                        index.insert(next.clone(), id);
+                        sets.push(next.clone());
+                        queue.push_back(next);
+                        id
+                    }
+                };
+                row.push(Some(id));
+            }
+
+            transitions.push(row);
+        }
+
+        let accepting = sets.iter().map(|set| set.contains(&self.accept)).collect();
+        Ok(Dfa { transitions, accepting })
+    }
+
+    /// Cierre por transiciones vacías.
+    fn closure(&self, mut set: BTreeSet<usize>) -> BTreeSet<usize> {
+        let mut pending: Vec<usize> = set.iter().copied().collect();
+
+        while let Some(s) = pending.pop() {
+            for &next in &self.states[s].epsilon {
+                if set.insert(next) {
+                    pending.push(next);
+                }
+            }
+        }
+
+        set
+    }
+}
+
+/// Construcción de Thompson del autómata a partir del AST del patrón. Cada método añade un
+/// fragmento que empieza en `from` y devuelve su estado final.
+struct Builder<'a, F> {
+    alphabet: &'a GraphemeSet,
+    unit: Unit,
+    max_length: usize,
+    resolve: &'a mut F,
+}
+
+impl<F> Builder<'_, F>
+where
+    F: FnMut(&str) -> Result<GraphemeSet>,
+{
+    fn alternatives(&mut self, nfa: &mut Nfa, from: usize, alternatives: &[Vec<Node>]) -> Result<usize> {
+        if let [sequence] = alternatives {
+            return self.sequence(nfa, from, sequence);
+        }
+
+        let end = nfa.state();
+        for sequence in alternatives {
+            let start = nfa.state();
+            nfa.states[from].epsilon.push(start);
+            let last = self.sequence(nfa, start, sequence)?;
+            nfa.states[last].epsilon.push(end);
+        }
+
+        Ok(end)
+    }
+
+    fn sequence(&mut self, nfa: &mut Nfa, from: usize, nodes: &[Node]) -> Result<usize> {
+        let mut current = from;
+        for node in nodes {
+            current = self.node(nfa, current, node)?;
+        }
+        Ok(current)
+    }
+
+    fn node(&mut self, nfa: &mut Nfa, from: usize, node: &Node) -> Result<usize> {
+        match node {
+            Node::Block {
+                name,
+                negated,
+                quantifier,
+            } => {
+                let chars = (self.resolve)(name)?;
+                let chars = if *negated {
+                    self.alphabet.difference(&chars)
+                } else {
+                    chars
+                };
+
+                let class = nfa.class(chars);
+                self.repeat(nfa, from, *quantifier, |_, nfa, from| Ok(nfa.edge(from, class)))
+            }
+            Node::Group {
+                alternatives,
+                quantifier,
+            } => self.repeat(nfa, from, *quantifier, |builder, nfa, from| {
+                builder.alternatives(nfa, from, alternatives)
+            }),
+            Node::Literal(text) => {
+                let mut current = from;
+                for element in self.unit.split(text) {
+                    let class = nfa.class(GraphemeSet::from(element));
+                    current = nfa.edge(current, class);
+                }
+                Ok(current)
+            }
+            Node::Wildcard => {
+                let class = nfa.class(self.alphabet.clone());
+                let wildcard = Quantifier { min: 0, max: None };
+                self.repeat(nfa, from, wildcard, |_, nfa, from| Ok(nfa.edge(from, class)))
+            }
+        }
+    }
+
+    /// Repite entre `min` y `max` veces el fragmento que construye `fragment`. Cada repetición
+    /// añade al menos un carácter o ninguno, así que basta con `max_length` copias. Cada copia es
+    /// un fragmento nuevo, por lo que se comprueba que el autómata no supere `MAX_STATES`.
+    fn repeat<G>(&mut self, nfa: &mut Nfa, from: usize, quantifier: Quantifier, mut fragment: G) -> Result<usize>
+    where
+        G: FnMut(&mut Self, &mut Nfa, usize) -> Result<usize>,
+    {
+        let mut copy = |builder: &mut Self, nfa: &mut Nfa, from| {
+            let last = fragment(builder, nfa, from)?;
+            if nfa.states.len() > MAX_STATES {
+                return Err(too_complex());
+            }
+            Ok(last)
+        };
+
+        let mut current = from;
+        for _ in 0..quantifier.min.min(self.max_length) {
+            current = copy(self, nfa, current)?;
+        }
+
+        let end = nfa.state();
+        match quantifier.max {
+            None => {
+                // Bucle: el fragmento puede repetirse cualquier número de veces.
+                let start = nfa.state();
+                nfa.states[current].epsilon.push(start);
+                nfa.states[start].epsilon.push(end);
+                let last = copy(self, nfa, start)?;
+                nfa.states[last].epsilon.push(start);
+            }
+            Some(max) => {
+                for _ in quantifier.min..max.min(self.max_length) {
+                    nfa.states[current].epsilon.push(end);
+                    current = copy(self, nfa, current)?;
+                }
+                nfa.states[current].epsilon.push(end);
+            }
+        }
+
+        Ok(end)
+    }
+}
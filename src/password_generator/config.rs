@@ -1,10 +1,25 @@
-use std::{collections::HashMap, fs, path::Path};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt, fs,
+    path::Path,
+    str::FromStr,
+};
 
-use serde::Deserialize;
+use serde::{de, Deserialize, Deserializer};
+use unicode_segmentation::UnicodeSegmentation;
 use uuid::Uuid;
 
+use super::entropy::{Entropy, Prepared};
 use super::error::{PasswordGenError, Result};
-use crate::utils::parse_unicode;
+use super::pattern::{parse_pattern, Pattern};
+use crate::grapheme_set::GraphemeSet;
+use crate::keyboard::{self, Layout, LAYOUTS};
+use crate::range_set::RangeSet;
+use crate::utils::{
+    evaluate_set_expression, filter_unsafe, find_unsafe, is_known_language, locale_alphabet,
+    parse_graphemes_normalized, parse_unicode_normalized, preset, preset_reference, remove_ambiguous, remove_dependent,
+    remove_unnormalized, NormalForm, UnsafeKind, UtilsError,
+};
 
 /// Define un trait para validaciones dentro de la configuración.
 /// Cada struct que deba validar sus propios campos implementará este trait.
@@ -13,6 +28,11 @@ pub trait Validator {
     fn validate(&mut self) -> Result<()>;
 }
 
+/// Indica si `name` tiene forma de código ISO 639: dos o tres letras ASCII minúsculas.
+fn is_language_code(name: &str) -> bool {
+    (2..=3).contains(&name.len()) && name.chars().all(|c| c.is_ascii_lowercase())
+}
+
 /// Representa la configuración general, cargada desde un archivo TOML.
 #[derive(Deserialize, Clone, Debug)]
 pub struct Config {
@@ -20,41 +40,392 @@ pub struct Config {
     pub profile: Profile,
     pub rules: Rules,
     pub charset: Charset,
+
+    /// Secciones `[es]`, `[de]`...: charsets propios de cada idioma de `profile.lang`. Se
+    /// rellenan en la validación a partir de `sections`.
+    #[serde(skip)]
+    pub languages: HashMap<String, HashMap<String, CharsetConstraint>>,
+
+    /// Resto de tablas del archivo, sin interpretar. Se validan después para que un error
+    /// nombre la tabla que lo provoca.
+    #[serde(flatten)]
+    sections: HashMap<String, toml::Value>,
+
+    /// Avisos de la validación, en el orden en que se detectaron.
+    #[serde(skip)]
+    warnings: Vec<ConfigWarning>,
+
+    /// Generador exacto construido en la validación, que reutilizan `entropy` y `GenPassword`.
+    #[serde(skip)]
+    pub(crate) prepared: Option<Prepared>,
 }
 
 impl Config {
     /// Carga la configuración desde un archivo en `path` y la valida.
     /// Retorna un `Result<Self>` o un `PasswordGenError`.
     pub fn from_file<T: AsRef<Path>>(path: T) -> Result<Self> {
-        let contents = fs::read_to_string(path)?;
-        let mut config = toml::from_str::<Config>(&contents)?;
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// Avisos de la validación: partes de la configuración que se aceptan pero no se usarán tal
+    /// como están escritas.
+    pub fn warnings(&self) -> &[ConfigWarning] {
+        &self.warnings
+    }
+
+    /// Calcula la entropía real de las contraseñas que genera esta configuración. Tras la
+    /// validación ya está calculada.
+    pub fn entropy(&self) -> Result<Entropy> {
+        match &self.prepared {
+            Some(prepared) => Ok(prepared.entropy.clone()),
+            None => Entropy::of(self),
+        }
+    }
+
+    /// Construye el alfabeto final a partir de `[charset]`, aplicando primero `exclude` y después
+    /// `include` (ver secciones 8.2 y 8.3 de PASSWORD.md). Con `avoid-ambiguous`, los caracteres
+    /// que se confunden entre sí se quitan junto con los de `exclude`. Con `keyboard`, el
+    /// resultado se limita a los caracteres que se pueden escribir en todas las distribuciones
+    /// indicadas.
+    pub fn get_charset(&self) -> Result<GraphemeSet> {
+        let mut alphabet = GraphemeSet::new();
+
+        // 1. Conjuntos base definidos en `[charset]`.
+        for chars in self.charset.resolved.values() {
+            alphabet = alphabet.union(chars);
+        }
+
+        // 2. Aplicar `exclude` y quitar los caracteres ambiguos.
+        alphabet = alphabet.difference(&self.rules.excluded);
+        alphabet = alphabet.difference(&self.rules.ambiguous);
+
+        // 3. Aplicar `include`, incluso si los caracteres estaban excluidos.
+        alphabet = alphabet.union(&self.rules.included);
+
+        // 4. Quitar lo que no se puede escribir en todas las distribuciones de `keyboard`.
+        if let Some(typeable) = &self.rules.typeable {
+            alphabet = alphabet.intersection(typeable);
+        }
+
+        if alphabet.is_empty() {
+            return Err(PasswordGenError::InvalidConfig(
+                "The final character set is empty after applying include/exclude".into(),
+            ));
+        }
+
+        Ok(alphabet)
+    }
+
+    /// Resuelve las restricciones por charset de `[rules]` (p. ej. `uppercase = { min = 2 }`)
+    /// contra el alfabeto final. Cada charset solo cuenta los caracteres que siguen
+    /// disponibles tras aplicar `include`/`exclude`.
+    /// Las reglas se devuelven ordenadas por nombre para que el resultado sea determinista.
+    pub fn get_charset_rules(&self, alphabet: &GraphemeSet) -> Result<Vec<CharsetRule>> {
+        let Some(charsets_rules) = &self.rules.charsets_rules else {
+            return Ok(Vec::new());
+        };
+
+        let mut rules = Vec::with_capacity(charsets_rules.len());
+        for (name, constraint) in charsets_rules {
+            let chars = self.get_named_charset(name, alphabet)?;
+            let (min, max) = constraint.bounds();
+
+            rules.push(CharsetRule {
+                name: name.clone(),
+                chars,
+                min,
+                max,
+            });
+        }
+
+        rules.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(rules)
+    }
+
+    /// Comprueba que el patrón solo use charsets definidos, que sus literales no tengan
+    /// caracteres no imprimibles ni ambiguos, se puedan escribir en las distribuciones de
+    /// `keyboard` y no cambien al normalizar la contraseña, y que sea compatible con `length`
+    /// (sección 8.1 de PASSWORD.md).
+    fn validate_pattern(&self, pattern: &Pattern) -> Result<()> {
+        if let Some(name) = pattern.charset_names().into_iter().find(|name| !self.has_charset(name)) {
+            return Err(PasswordGenError::CharsetNotFound(name.into()));
+        }
+
+        // Los literales se copian tal cual, así que no pueden cambiar al normalizar la contraseña.
+        // Tampoco pasan por el filtro de no imprimibles: solo pueden llevar caracteres de las
+        // categorías que algún charset permite con `allow`.
+        let form = self.rules.normalization;
+        let unit = self.rules.unit;
+        let allowed = self.charset.allowed();
+
+        // Con `avoid-ambiguous`, un literal tampoco puede llevar elementos que se confundan con
+        // otro del alfabeto o de los literales, salvo que `include` los añada expresamente.
+        let ambiguous = if self.rules.avoid_ambiguous {
+            let mut candidates = self.get_charset()?.union(&self.rules.ambiguous);
+            for literal in pattern.literals() {
+                candidates = candidates.union(&unit.split(literal).into_iter().collect());
+            }
+            remove_ambiguous(&mut candidates).difference(&self.rules.included)
+        } else {
+            GraphemeSet::new()
+        };
+
+        for literal in pattern.literals() {
+            if let Some((_, c, kind)) = find_unsafe(literal)
+                .into_iter()
+                .find(|(_, _, kind)| !allowed.contains(kind))
+            {
+                return Err(PasswordGenError::InvalidConfig(format!(
+                    "The pattern literal {:?} contains the {} character U+{:04X}; add \"{}\" to allow in a charset to use it",
+                    literal,
+                    kind.name(),
+                    c as u32,
+                    kind.name()
+                )));
+            }
+            if let (Some(keyboard), Some(typeable)) = (&self.rules.keyboard, &self.rules.typeable) {
+                if let Some(c) = literal.chars().find(|&c| !typeable.chars().contains(c)) {
+                    return Err(PasswordGenError::InvalidConfig(format!(
+                        "The pattern literal \"{}\" contains '{}', which cannot be typed on the '{}' keyboard layout",
+                        literal,
+                        c,
+                        keyboard.missing(c).unwrap_or_default()
+                    )));
+                }
+            }
+            if let Some(element) = unit
+                .split(literal)
+                .into_iter()
+                .find(|element| ambiguous.contains(element))
+            {
+                return Err(PasswordGenError::InvalidConfig(format!(
+                    "The pattern literal \"{}\" contains '{}', which avoid-ambiguous removes because it can be confused with another character",
+                    literal, element
+                )));
+            }
+            if !unit.split(literal).into_iter().all(|element| form.is_stable(element)) {
+                return Err(PasswordGenError::InvalidConfig(format!(
+                    "The pattern literal \"{}\" would change under {} normalization",
+                    literal,
+                    form.name()
+                )));
+            }
+        }
+
+        let length = &self.rules.length;
+        if pattern.min_length(unit) > length.max() {
+            return Err(PasswordGenError::InvalidConfig(format!(
+                "The pattern requires at least {} characters but the maximum length is {}",
+                pattern.min_length(unit),
+                length.max()
+            )));
+        }
+
+        // Sin comodín no hay forma de rellenar hasta la longitud mínima.
+        if let Some(max) = pattern.max_length(unit) {
+            if max < length.min() {
+                return Err(PasswordGenError::InvalidConfig(format!(
+                    "The pattern allows at most {} characters but the minimum length is {}",
+                    max,
+                    length.min()
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Secciones de los idiomas de `profile.lang`, en ese orden. Un idioma sin sección propia
+    /// debe tener alfabetos predefinidos. Las demás tablas se ignoran con un aviso.
+    fn active_languages(&mut self) -> Result<Vec<(String, HashMap<String, CharsetConstraint>)>> {
+        let mut names: Vec<&String> = self.sections.keys().collect();
+        names.sort();
+        for name in names {
+            let value = &self.sections[name];
+            if self.profile.lang.contains(name) {
+                let table = value.as_table().ok_or_else(|| {
+                    PasswordGenError::InvalidConfig(format!("Language section [{}] must be a table of charsets", name))
+                })?;
+                let mut section = HashMap::with_capacity(table.len());
+                for (key, charset) in table {
+                    let constraint = charset.clone().try_into().map_err(|e: toml::de::Error| {
+                        PasswordGenError::InvalidConfig(format!(
+                            "Invalid charset '{}' in section [{}]: {}",
+                            key,
+                            name,
+                            e.message()
+                        ))
+                    })?;
+                    section.insert(key.clone(), constraint);
+                }
+                self.languages.insert(name.clone(), section);
+            } else if is_language_code(name) && value.is_table() {
+                self.warnings.push(ConfigWarning::IgnoredLanguage(name.clone()));
+            } else {
+                self.warnings.push(ConfigWarning::UnknownSection(name.clone()));
+            }
+        }
+
+        let mut languages = Vec::with_capacity(self.profile.lang.len());
+        for lang in &self.profile.lang {
+            let section = self.languages.get(lang);
+            if section.is_none() && !is_known_language(lang) {
+                return Err(PasswordGenError::InvalidConfig(format!(
+                    "Language '{}' has no [{}] section and no built-in alphabet",
+                    lang, lang
+                )));
+            }
+            languages.push((lang.clone(), section.cloned().unwrap_or_default()));
+        }
+
+        Ok(languages)
+    }
+
+    /// Indica si `name` es un charset de `[charset]` o una referencia `@nombre` válida.
+    fn has_charset(&self, name: &str) -> bool {
+        self.charset.get(name).is_some()
+    }
+
+    /// Caracteres del charset `name` (definido en `[charset]` o referenciado con `@nombre`) que
+    /// siguen disponibles en el alfabeto final. Es el conjunto que usan tanto las restricciones
+    /// por charset como los bloques del patrón.
+    pub fn get_named_charset(&self, name: &str, alphabet: &GraphemeSet) -> Result<GraphemeSet> {
+        let members = self
+            .charset
+            .get(name)
+            .ok_or_else(|| PasswordGenError::CharsetNotFound(name.into()))?;
+
+        Ok(alphabet.intersection(&members))
+    }
+
+    /// Restricciones de posición de `first`, `last` y `positions`, ordenadas por posición.
+    pub fn get_position_rules(&self) -> Vec<PositionRule> {
+        self.rules
+            .position_sets
+            .iter()
+            .map(|(&index, chars)| PositionRule {
+                index,
+                chars: chars.clone(),
+            })
+            .collect()
+    }
+}
+
+/// Analiza y valida una configuración escrita en TOML, igual que `Config::from_file`.
+impl FromStr for Config {
+    type Err = PasswordGenError;
+
+    fn from_str(contents: &str) -> Result<Self> {
+        let mut config = toml::from_str::<Config>(contents)?;
         config.validate()?;
         Ok(config)
     }
+}
 
-    pub fn get_charset(&self) -> Result<Vec<char>> {
-        if let Some(charsets_rules) = &self.rules.charsets_rules {
-            for (name, charset_rule) in charsets_rules {
-                let charset = &self.charset.charsets.get(name).unwrap();
+/// Restricción de cantidad ya resuelta para un charset: el charset `name` debe aparecer
+/// entre `min` y `max` veces (sin límite superior si `max` es `None`).
+#[derive(Clone, Debug)]
+pub struct CharsetRule {
+    pub name: String,
+    pub chars: GraphemeSet,
+    pub min: usize,
+    pub max: Option<usize>,
+}
 
-                let charset = match charset {
-                    CharsetConstraint::Multiple(v) => {
-                        let mut valid_chars = Vec::new();
+/// Restricción de posición ya resuelta: el elemento en la posición `index` debe pertenecer a
+/// `chars`. Las posiciones se cuentan desde 0; las negativas, desde el final (`-1` es la última).
+#[derive(Clone, Debug)]
+pub struct PositionRule {
+    pub index: isize,
+    pub chars: GraphemeSet,
+}
 
-                        for x in v {
-                            valid_chars.extend(parse_unicode(x).unwrap());
-                        }
+/// Aviso de la validación: la configuración es válida, pero parte de ella no se usará tal como
+/// está escrita. `Display` da el texto del aviso.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigWarning {
+    /// Elementos descartados de `source` (un charset o `include`) por uno de los filtros.
+    /// `description` dice cuáles son y `hint`, cómo conservarlos si se puede.
+    Removed {
+        source: String,
+        description: String,
+        elements: Vec<String>,
+        hint: Option<String>,
+    },
+    /// Caracteres de `exclude` que `include` vuelve a añadir.
+    Reintroduced(String),
+    /// Caracteres de `include` que no se pueden escribir en todas las distribuciones de `keyboard`.
+    Untypeable(String),
+    /// Caracteres ambiguos que `include` vuelve a añadir.
+    AmbiguousIncluded(String),
+    /// Sección de un idioma que no está en `profile.lang`.
+    IgnoredLanguage(String),
+    /// Clave o tabla de primer nivel que no es una sección conocida ni un idioma.
+    UnknownSection(String),
+}
 
-                        valid_chars
-                    }
-                    CharsetConstraint::One(v) => Vec::from(parse_unicode(v).unwrap()),
-                };
+impl fmt::Display for ConfigWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigWarning::Removed {
+                source,
+                description,
+                elements,
+                hint,
+            } => {
+                // Solo se muestran los primeros elementos, como puntos de código.
+                const SHOWN: usize = 8;
+                let mut list: Vec<String> = elements
+                    .iter()
+                    .take(SHOWN)
+                    .map(|element| {
+                        let codes: Vec<String> = element.chars().map(|c| format!("U+{:04X}", c as u32)).collect();
+                        codes.join(" ")
+                    })
+                    .collect();
+                if elements.len() > SHOWN {
+                    list.push("...".into());
+                }
 
-                println!("{:?}", charset);
+                write!(
+                    f,
+                    "Removed {} {} from {}: {}",
+                    elements.len(),
+                    description,
+                    source,
+                    list.join(", ")
+                )?;
+                if let Some(hint) = hint {
+                    write!(f, " ({})", hint)?;
+                }
+                write!(f, ".")
             }
+            ConfigWarning::Reintroduced(chars) => {
+                write!(f, "Characters {} were excluded but reintroduced via include.", chars)
+            }
+            ConfigWarning::Untypeable(chars) => write!(
+                f,
+                "Characters {} from include cannot be typed on every keyboard layout and will not be used.",
+                chars
+            ),
+            ConfigWarning::AmbiguousIncluded(chars) => {
+                write!(
+                    f,
+                    "Characters {} are ambiguous but were reintroduced via include.",
+                    chars
+                )
+            }
+            ConfigWarning::IgnoredLanguage(lang) => write!(
+                f,
+                "Section [{}] is ignored because '{}' is not listed in profile.lang.",
+                lang, lang
+            ),
+            ConfigWarning::UnknownSection(name) => write!(
+                f,
+                "Section [{}] is ignored: it is neither a configuration section nor a language in profile.lang.",
+                name
+            ),
         }
-
-        Ok(Vec::new())
     }
 }
 
@@ -77,18 +448,56 @@ impl Validator for Config {
         }
 
         self.profile.validate()?;
+        self.charset.unit = self.rules.unit;
+        self.charset.normalization = self.rules.normalization;
+        self.charset.languages = self.active_languages()?;
         self.charset.validate()?;
+        self.warnings.append(&mut self.charset.warnings);
         self.rules.validate()?;
+        self.rules.validate_char_lists(&self.charset)?;
+        self.warnings.append(&mut self.rules.warnings);
+        self.rules.validate_positions(&self.charset)?;
 
+        // Las reglas por charset solo pueden referenciar charsets definidos en `[charset]` o presets.
+        if let Some(charsets_rules) = &self.rules.charsets_rules {
+            if let Some(name) = charsets_rules.keys().find(|name| !self.has_charset(name)) {
+                return Err(PasswordGenError::CharsetNotFound(name.clone()));
+            }
+        }
+
+        if let Some(pattern) = self.rules.pattern()? {
+            self.validate_pattern(&pattern)?;
+        }
+
+        // Las reglas pueden ser incompatibles entre sí en todas las longitudes (un charset con
+        // mínimo cuyos caracteres se excluyeron, posiciones que el patrón no admite...): se
+        // comprueba contando, para que el error aparezca al cargar y no al generar. El recuento
+        // se guarda para no repetirlo al generar.
+        let prepared = Prepared::new(self)?;
+
+        // La entropía real (no la aproximación `log2(alfabeto^longitud)`) debe alcanzar el mínimo.
+        if let Some(required) = self.rules.min_entropy_bits {
+            if prepared.entropy.bits < required.into() {
+                return Err(PasswordGenError::InsufficientEntropy {
+                    bits: prepared.entropy.bits,
+                    required,
+                });
+            }
+        }
+
+        self.prepared = Some(prepared);
         Ok(())
     }
 }
 
-/// Contiene información de perfil, como un identificador único (`id`) y un `name`.
+/// Contiene información de perfil, como un identificador único (`id`), un `name` y los idiomas
+/// (`lang`) cuyos alfabetos y secciones `[xx]` se usan.
 #[derive(Deserialize, Clone, Debug)]
 pub struct Profile {
     id: Uuid,
     name: String,
+    #[serde(default)]
+    lang: Vec<String>,
 }
 
 /// Valida la estructura `Profile` revisando que el ID no sea nulo y el nombre cumpla criterios.
@@ -115,6 +524,22 @@ impl Validator for Profile {
             )));
         }
 
+        // Códigos ISO 639-1 (o 639-2/3 para idiomas sin código de dos letras).
+        for (i, lang) in self.lang.iter().enumerate() {
+            if !is_language_code(lang) {
+                return Err(PasswordGenError::InvalidConfig(format!(
+                    "Invalid language code '{}': expected an ISO 639 code such as \"es\"",
+                    lang
+                )));
+            }
+            if self.lang[..i].contains(lang) {
+                return Err(PasswordGenError::InvalidConfig(format!(
+                    "Language '{}' is listed more than once",
+                    lang
+                )));
+            }
+        }
+
         Ok(())
     }
 }
@@ -123,34 +548,132 @@ impl Validator for Profile {
 /// incluyendo restricciones de longitud, caracteres permitidos y otras propiedades.
 #[derive(Deserialize, Clone, Debug)]
 pub struct Rules {
-    length: Constraint,
+    pub(crate) length: Constraint,
     include: Option<Vec<String>>,
     exclude: Option<Vec<String>>,
     #[serde(rename = "max-consecutive")]
-    max_consecutive: Option<u8>,
+    pub(crate) max_consecutive: Option<u8>,
+    /// Longitud máxima de las secuencias (`abc`, `321`) y los recorridos de teclado (`qwe`).
+    #[serde(rename = "max-sequence")]
+    pub(crate) max_sequence: Option<u8>,
     #[serde(rename = "min-entropy-bits")]
-    min_entropy_bits: Option<u8>,
+    pub(crate) min_entropy_bits: Option<u8>,
     pattern: Option<String>,
+    /// Elementos del alfabeto: caracteres o grafemas extendidos. Ver `Unit`.
+    #[serde(default)]
+    pub(crate) unit: Unit,
+    /// Forma normal bajo la que la contraseña no cambia. Ver `NormalForm`.
+    #[serde(default)]
+    pub(crate) normalization: NormalForm,
+    keyboard: Option<KeyboardConstraint>,
+    /// Caracteres permitidos en la primera y en la última posición.
+    first: Option<String>,
+    last: Option<String>,
+    /// Caracteres permitidos en posiciones concretas: `0` es la primera y `-1` la última.
+    positions: Option<BTreeMap<String, String>>,
+    /// Descarta los caracteres que se confunden con otro del alfabeto (`0` y `O`, `1` y `l`...).
+    #[serde(rename = "avoid-ambiguous", default)]
+    avoid_ambiguous: bool,
     #[serde(flatten)]
     charsets_rules: Option<HashMap<String, RulesConstraint>>,
+
+    /// Caracteres de `include` y `exclude` ya resueltos en `validate_char_lists`.
+    #[serde(skip)]
+    included: GraphemeSet,
+    #[serde(skip)]
+    excluded: GraphemeSet,
+    /// Caracteres que se pueden escribir en todas las distribuciones de `keyboard`, calculados
+    /// en `validate`.
+    #[serde(skip)]
+    typeable: Option<GraphemeSet>,
+    /// Caracteres que `avoid-ambiguous` quita del alfabeto, calculados en `validate_char_lists`.
+    #[serde(skip)]
+    ambiguous: GraphemeSet,
+    /// Caracteres permitidos en cada posición, calculados en `validate_positions`. Si una
+    /// posición tiene varias restricciones, se guarda su intersección.
+    #[serde(skip)]
+    position_sets: BTreeMap<isize, GraphemeSet>,
+    /// Avisos de `validate_char_lists`, que `Config::validate` recoge.
+    #[serde(skip)]
+    warnings: Vec<ConfigWarning>,
 }
 
 impl Rules {
+    /// Devuelve el patrón de `rules.pattern` ya analizado, si existe.
+    pub fn pattern(&self) -> Result<Option<Pattern>> {
+        self.pattern.as_deref().map(parse_pattern).transpose()
+    }
+
+    /// Distribuciones en las que `max-sequence` busca recorridos de teclado: las de `keyboard`
+    /// o, si no se indica, la estadounidense.
+    pub(crate) fn walk_layouts(&self) -> Vec<&'static Layout> {
+        match &self.keyboard {
+            Some(keyboard) => keyboard
+                .layouts()
+                .iter()
+                .filter_map(|code| keyboard::layout(code))
+                .collect(),
+            None => keyboard::layout("us").into_iter().collect(),
+        }
+    }
+
     /// Función auxiliar para validar las listas de caracteres incluidos/excluidos.
-    /// Usa la función `parse_unicode` para transformar las cadenas en chars.
-    fn validate_char_list(&self, list: &Option<Vec<String>>, list_name: &str) -> Result<Vec<char>> {
+    /// Usa `Charset::parse_item` para transformar las cadenas en chars, de modo que las listas
+    /// pueden referenciar charsets de `[charset]` y presets con `@nombre`. Con `safe`, los
+    /// caracteres no imprimibles de las cadenas (no de los charsets referenciados) se descartan,
+    /// igual que, con `unit = "grapheme"`, los que no forman un grafema por sí solos.
+    fn validate_char_list(
+        &self,
+        list: &Option<Vec<String>>,
+        list_name: &str,
+        charset: &Charset,
+        safe: bool,
+        warnings: &mut Vec<ConfigWarning>,
+    ) -> Result<GraphemeSet> {
         if let Some(items) = list {
             if !items.is_empty() {
-                let mut valid_chars = Vec::new();
+                let mut valid_chars = GraphemeSet::new();
+                let mut removed = BTreeMap::new();
 
                 for item in items {
-                    let chars = parse_unicode(item).map_err(|e| {
-                        PasswordGenError::InvalidConfig(format!("Error procesando '{}' en {}: {}", item, list_name, e))
-                    })?;
-                    valid_chars.extend(chars);
+                    let chars = charset
+                        .parse_item(item, |mut chars| {
+                            if safe {
+                                filter(&mut chars, &[], self.unit, self.normalization, &mut removed);
+                            }
+                            chars
+                        })
+                        .map_err(|e| {
+                            PasswordGenError::InvalidConfig(format!(
+                                "Error procesando '{}' en {}: {}",
+                                item, list_name, e
+                            ))
+                        })?;
+                    valid_chars = valid_chars.union(&chars);
                 }
+                warnings.extend(removed_warnings(list_name, &removed, None));
 
                 if valid_chars.is_empty() {
+                    // Si el filtro de no imprimibles lo descartó todo, se dice cómo conservarlos:
+                    // `include` no admite `allow`, pero un charset sí.
+                    if let Some((Removal::Unsafe(kind), elements)) = removed.iter().next() {
+                        let codes: Vec<String> = elements
+                            .iter()
+                            .map(|element| {
+                                let codes: Vec<String> =
+                                    element.chars().map(|c| format!("U+{:04X}", c as u32)).collect();
+                                codes.join(" ")
+                            })
+                            .collect();
+                        return Err(PasswordGenError::InvalidConfig(format!(
+                            "Every character in {} was removed by the unsafe character filter ({}: {}). \
+                             Define them in a charset with allow = [\"{}\"] and add it with @name to keep them",
+                            list_name,
+                            kind.name(),
+                            codes.join(", "),
+                            kind.name()
+                        )));
+                    }
                     return Err(PasswordGenError::InvalidConfig(format!(
                         "No se generaron caracteres Unicode válidos en {}",
                         list_name
@@ -160,12 +683,116 @@ impl Rules {
                 return Ok(valid_chars);
             }
         }
-        Ok(Vec::new())
+        Ok(GraphemeSet::new())
+    }
+
+    /// Valida las listas `include` y `exclude`, que pueden referenciar charsets, por lo que
+    /// necesitan `[charset]` ya resuelto.
+    fn validate_char_lists(&mut self, charset: &Charset) -> Result<()> {
+        // 1. Validar caracteres incluidos/excluidos. Solo `include` puede añadir caracteres no
+        //    imprimibles, así que el filtro no se aplica a `exclude`.
+        let mut warnings = Vec::new();
+        let valid_includes = self.validate_char_list(&self.include, "include", charset, true, &mut warnings)?;
+        if valid_includes.is_empty() {
+            self.include = None;
+        }
+
+        let valid_excludes = self.validate_char_list(&self.exclude, "exclude", charset, false, &mut warnings)?;
+        if valid_excludes.is_empty() {
+            self.exclude = None;
+        }
+
+        // 2. Verificar colisiones entre include/exclude.
+        if !valid_includes.is_empty() && !valid_excludes.is_empty() {
+            let collisions = valid_includes.intersection(&valid_excludes);
+
+            if !collisions.is_empty() {
+                warnings.push(ConfigWarning::Reintroduced(collisions.iter().collect()));
+            }
+        }
+
+        // 3. Avisar de los caracteres incluidos que `keyboard` va a descartar.
+        if let Some(typeable) = &self.typeable {
+            let untypeable = valid_includes.difference(typeable);
+            if !untypeable.is_empty() {
+                warnings.push(ConfigWarning::Untypeable(untypeable.iter().collect()));
+            }
+        }
+
+        // 4. Buscar los caracteres ambiguos entre los que quedarían en el alfabeto. Los que se
+        //    incluyen explícitamente se mantienen, igual que con `exclude`.
+        if self.avoid_ambiguous {
+            let mut candidates = charset
+                .resolved
+                .values()
+                .fold(GraphemeSet::new(), |all, chars| all.union(chars))
+                .difference(&valid_excludes)
+                .union(&valid_includes);
+            if let Some(typeable) = &self.typeable {
+                candidates = candidates.intersection(typeable);
+            }
+
+            let ambiguous = remove_ambiguous(&mut candidates);
+            let reintroduced = ambiguous.intersection(&valid_includes);
+            if !reintroduced.is_empty() {
+                warnings.push(ConfigWarning::AmbiguousIncluded(reintroduced.iter().collect()));
+            }
+            self.ambiguous = ambiguous.difference(&valid_includes);
+        }
+
+        self.included = valid_includes;
+        self.excluded = valid_excludes;
+        self.warnings = warnings;
+        Ok(())
+    }
+
+    /// Resuelve `first`, `last` y `positions`, que pueden referenciar charsets igual que
+    /// `include`, y comprueba que cada posición quepa en la longitud máxima.
+    fn validate_positions(&mut self, charset: &Charset) -> Result<()> {
+        let mut constraints: Vec<(isize, String, &str)> = Vec::new();
+        if let Some(first) = &self.first {
+            constraints.push((0, "first".into(), first));
+        }
+        if let Some(last) = &self.last {
+            constraints.push((-1, "last".into(), last));
+        }
+        for (key, item) in self.positions.iter().flatten() {
+            let index = key.trim().parse::<isize>().map_err(|_| {
+                PasswordGenError::InvalidConfig(format!(
+                    "Invalid position '{}': expected an integer such as 0 or -1",
+                    key
+                ))
+            })?;
+            constraints.push((index, format!("positions.{}", key), item));
+        }
+
+        let max = self.length.max();
+        let mut position_sets: BTreeMap<isize, GraphemeSet> = BTreeMap::new();
+        for (index, source, item) in constraints {
+            if index.unsigned_abs() >= max + usize::from(index < 0) {
+                return Err(PasswordGenError::InvalidConfig(format!(
+                    "Position {} in {} is outside the maximum length ({})",
+                    index, source, max
+                )));
+            }
+
+            let chars = charset.parse_item(item, |chars| chars).map_err(|e| {
+                PasswordGenError::InvalidConfig(format!("Error procesando '{}' en {}: {}", item, source, e))
+            })?;
+            position_sets
+                .entry(index)
+                .and_modify(|allowed| *allowed = allowed.intersection(&chars))
+                .or_insert(chars);
+        }
+
+        self.position_sets = position_sets;
+        Ok(())
     }
 }
 
-/// Valida `Rules`, principalmente ajustando la restricción de longitud y revisando las listas
-/// de caracteres incluidos/excluidos.
+/// Valida `Rules`, principalmente ajustando la restricción de longitud y revisando las
+/// restricciones por charset. Las listas `include`/`exclude` se validan aparte, en
+/// `validate_char_lists`, una vez resuelto `[charset]`.
 impl Validator for Rules {
     fn validate(&mut self) -> Result<()> {
         // 1. Validar la restricción de longitud.
@@ -193,49 +820,61 @@ impl Validator for Rules {
                     ));
                 }
             }
+            Constraint::Auto => {}
         }
-
-        // 2. Validar caracteres incluidos/excluidos.
-        let valid_includes = self.validate_char_list(&self.include, "include")?;
-        if valid_includes.is_empty() {
-            self.include = None;
-        }
-
-        let valid_excludes = self.validate_char_list(&self.exclude, "exclude")?;
-        if valid_excludes.is_empty() {
-            self.exclude = None;
+        if self.length.max() > MAX_LENGTH {
+            return Err(PasswordGenError::InvalidConfig(format!(
+                "Length cannot be greater than {}",
+                MAX_LENGTH
+            )));
         }
 
-        // 3. Verificar colisiones entre include/exclude.
+        // 2. Validar las restricciones por charset.
+        if let Some(charsets_rules) = &self.charsets_rules {
+            let mut min_total = 0;
 
-        if !valid_includes.is_empty() && !valid_excludes.is_empty() {
-            let include_set: std::collections::HashSet<_> = valid_includes.iter().collect();
-            let exclude_set: std::collections::HashSet<_> = valid_excludes.iter().collect();
-
-            let collisions: Vec<_> = include_set.intersection(&exclude_set).collect();
+            for (name, constraint) in charsets_rules {
+                let (min, max) = constraint.bounds();
+                if max.is_some_and(|max| min > max) {
+                    return Err(PasswordGenError::InvalidConfig(format!(
+                        "Minimum count cannot be greater than maximum count for charset '{}'",
+                        name
+                    )));
+                }
+                min_total += min;
+            }
 
-            if !collisions.is_empty() {
-                let colliding_chars: String = collisions.iter().map(|&&c| c).collect();
-                // In the future
-                // pub enum ValidationResult {
-                //     Ok,
-                //     Warnings(Vec<String>),
-                //     Error(PasswordGenError),
-                // }
-                println!(
-                    "[WARN] Characters {} were excluded but reintroduced via include.",
-                    colliding_chars
-                );
+            if min_total > self.length.max() {
+                return Err(PasswordGenError::InvalidConfig(format!(
+                    "The sum of charset minimums ({}) exceeds the maximum length ({})",
+                    min_total,
+                    self.length.max()
+                )));
             }
         }
 
-        // 4. Ajustar restricciones opcionales a `None` si no son válidas.
+        // 3. Validar la sintaxis del patrón.
+        self.pattern()?;
+
+        // 4. Resolver las distribuciones de teclado.
+        self.typeable = match &self.keyboard {
+            Some(keyboard) => Some(keyboard.typeable()?),
+            None => None,
+        };
+
+        // 5. Ajustar restricciones opcionales a `None` si no son válidas.
         if let Some(value) = self.max_consecutive {
             if value == 0 {
                 self.max_consecutive = None
             }
         }
 
+        if let Some(value) = self.max_sequence {
+            if value == 0 {
+                self.max_sequence = None
+            }
+        }
+
         if let Some(value) = self.min_entropy_bits {
             if value == 0 {
                 self.min_entropy_bits = None
@@ -246,12 +885,162 @@ impl Validator for Rules {
     }
 }
 
-/// Define la restricción de longitud, que puede ser un rango (con min y max) o un valor exacto.
+/// Elementos con los que se construye la contraseña y en los que se mide su longitud.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Unit {
+    /// Caracteres Unicode (`char`). Un literal como `"🇪🇸"` aporta sus dos caracteres por separado.
+    #[default]
+    Char,
+    /// Grafemas extendidos, lo que el usuario percibe como un carácter: una bandera, una familia
+    /// unida con ZWJ o una letra con marcas combinantes son un único elemento. `length`,
+    /// `max-consecutive`, `max-sequence`, las restricciones por charset y de posición y la
+    /// entropía cuentan grafemas.
+    Grapheme,
+}
+
+impl Unit {
+    /// Divide `text` en elementos de esta unidad.
+    pub fn split(self, text: &str) -> Vec<&str> {
+        match self {
+            Unit::Char => text.char_indices().map(|(i, c)| &text[i..i + c.len_utf8()]).collect(),
+            Unit::Grapheme => text.graphemes(true).collect(),
+        }
+    }
+}
+
+/// Define la restricción de longitud, que puede ser un rango (con min y max), un valor exacto
+/// o la palabra reservada `"auto"`.
 #[derive(Deserialize, Clone, Debug)]
 #[serde(untagged)]
 pub enum Constraint {
-    Range { min: usize, max: usize },
+    Range {
+        min: usize,
+        max: usize,
+    },
     Exact(usize),
+    /// La longitud más corta que alcanza `min-entropy-bits` (o `DEFAULT_ENTROPY_BITS` si no se
+    /// define), buscada entre 1 y `MAX_AUTO_LENGTH`.
+    #[serde(deserialize_with = "deserialize_auto")]
+    Auto,
+}
+
+/// Entropía objetivo de `length = "auto"` cuando no se define `min-entropy-bits`.
+pub const DEFAULT_ENTROPY_BITS: u8 = 80;
+
+/// Longitud máxima que puede elegir `length = "auto"`.
+pub const MAX_AUTO_LENGTH: usize = 256;
+
+/// Idioma de `@lowercase`, `@uppercase` y `@letters` en un perfil sin `lang`.
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+/// Longitud máxima que admite `length`. El recuento exacto guarda, para cada posición, números
+/// que crecen con la longitud, así que la memoria crece con su cuadrado.
+pub const MAX_LENGTH: usize = 4096;
+
+/// Acepta únicamente la cadena `"auto"`.
+fn deserialize_auto<'de, D>(deserializer: D) -> std::result::Result<(), D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+    if value == "auto" {
+        Ok(())
+    } else {
+        Err(de::Error::invalid_value(de::Unexpected::Str(&value), &"\"auto\""))
+    }
+}
+
+impl Constraint {
+    /// Longitud mínima permitida.
+    pub fn min(&self) -> usize {
+        match *self {
+            Constraint::Range { min, .. } => min,
+            Constraint::Exact(value) => value,
+            Constraint::Auto => 1,
+        }
+    }
+
+    /// Longitud máxima permitida.
+    pub fn max(&self) -> usize {
+        match *self {
+            Constraint::Range { max, .. } => max,
+            Constraint::Exact(value) => value,
+            Constraint::Auto => MAX_AUTO_LENGTH,
+        }
+    }
+}
+
+/// Distribuciones de teclado en las que debe poder escribirse la contraseña: una lista de códigos
+/// (`["us", "es"]`) o la forma extendida, que permite excluir los caracteres que necesitan AltGr
+/// o teclas muertas (`{ layouts = ["es"], dead-keys = false }`).
+#[derive(Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum KeyboardConstraint {
+    Layouts(Vec<String>),
+    Detailed {
+        layouts: Vec<String>,
+        #[serde(default = "enabled")]
+        altgr: bool,
+        #[serde(rename = "dead-keys", default = "enabled")]
+        dead_keys: bool,
+    },
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl KeyboardConstraint {
+    fn layouts(&self) -> &[String] {
+        match self {
+            KeyboardConstraint::Layouts(layouts) | KeyboardConstraint::Detailed { layouts, .. } => layouts,
+        }
+    }
+
+    /// Si se admiten los caracteres que necesitan AltGr y los que necesitan teclas muertas.
+    fn options(&self) -> (bool, bool) {
+        match self {
+            KeyboardConstraint::Layouts(_) => (true, true),
+            KeyboardConstraint::Detailed { altgr, dead_keys, .. } => (*altgr, *dead_keys),
+        }
+    }
+
+    /// Primera distribución en la que no se puede escribir `c`.
+    fn missing(&self, c: char) -> Option<&str> {
+        let (altgr, dead_keys) = self.options();
+        self.layouts()
+            .iter()
+            .find(|code| keyboard::layout(code).is_some_and(|layout| !layout.typeable(altgr, dead_keys).contains(c)))
+            .map(String::as_str)
+    }
+
+    /// Caracteres que se pueden escribir en todas las distribuciones.
+    fn typeable(&self) -> Result<GraphemeSet> {
+        let (altgr, dead_keys) = self.options();
+
+        let mut typeable: Option<RangeSet> = None;
+        for code in self.layouts() {
+            let layout = keyboard::layout(code).ok_or_else(|| {
+                let known: Vec<&str> = LAYOUTS.iter().map(|layout| layout.code).collect();
+                PasswordGenError::InvalidConfig(format!(
+                    "Unknown keyboard layout '{}' (available: {})",
+                    code,
+                    known.join(", ")
+                ))
+            })?;
+
+            let chars = layout.typeable(altgr, dead_keys);
+            typeable = Some(match typeable {
+                Some(typeable) => typeable.intersection(&chars),
+                None => chars,
+            });
+        }
+
+        typeable
+            .map(GraphemeSet::from)
+            .ok_or_else(|| PasswordGenError::InvalidConfig("The keyboard list cannot be empty".into()))
+    }
 }
 
 /// Define la restricción de longitud, que puede ser un rango (con min y max) o un valor exacto.
@@ -262,27 +1051,332 @@ pub enum RulesConstraint {
     Exact(usize),
 }
 
+impl RulesConstraint {
+    /// Devuelve los límites `(min, max)` de la restricción; `max` es `None` si no hay límite superior.
+    pub fn bounds(&self) -> (usize, Option<usize>) {
+        match *self {
+            RulesConstraint::Range { min, max } => (min, max),
+            RulesConstraint::Exact(value) => (value, Some(value)),
+        }
+    }
+}
+
 /// Representa el conjunto de caracteres disponibles para la generación de contraseñas.
 /// El mapeo es flexible gracias al flatten del HashMap, que asocia nombres de charset
 /// con su tipo de restricción (`CharsetConstraint`).
+///
+/// Un charset puede referenciar a otro con `@nombre` (`alnum = ["@upper", "@lower", "@digits"]`).
+/// Si no existe un charset con ese nombre, `@nombre` es el alfabeto `nombre` de los idiomas
+/// activos (`@letters`) o un preset. Las referencias se resuelven una sola vez, en `validate`.
+///
+/// Los charsets también pueden definirse en las secciones de los idiomas activos (`[es]`,
+/// `[de]`...). Un nombre definido en varios sitios es la unión de todas sus definiciones.
 #[derive(Deserialize, Clone, Debug)]
 pub struct Charset {
     #[serde(flatten)]
     pub charsets: HashMap<String, CharsetConstraint>,
+
+    /// Caracteres de cada charset con las referencias ya resueltas.
+    #[serde(skip)]
+    resolved: BTreeMap<String, GraphemeSet>,
+
+    /// Copia de `rules.unit`, que decide cómo se dividen los literales.
+    #[serde(skip)]
+    unit: Unit,
+
+    /// Copia de `rules.normalization`, con la que se normalizan los literales y se filtran los
+    /// caracteres.
+    #[serde(skip)]
+    normalization: NormalForm,
+
+    /// Idiomas de `profile.lang`, en orden, con los charsets de su sección.
+    #[serde(skip)]
+    languages: Vec<(String, HashMap<String, CharsetConstraint>)>,
+
+    /// Avisos de la resolución de los charsets, que `Config::validate` recoge.
+    #[serde(skip)]
+    warnings: Vec<ConfigWarning>,
+}
+
+impl Charset {
+    /// Caracteres del charset `name`. Con `@` delante, `name` también puede ser un preset.
+    /// Devuelve `None` si no existe.
+    pub fn get(&self, name: &str) -> Option<GraphemeSet> {
+        if let Some(chars) = self.resolved.get(name) {
+            return Some(chars.clone());
+        }
+
+        let reference = preset_reference(name)?;
+        match self.resolved.get(reference) {
+            Some(chars) => Some(chars.clone()),
+            None => self.builtin(reference, None),
+        }
+    }
+
+    /// Conjunto predefinido `reference` (sin `@`): el alfabeto de un idioma concreto
+    /// (`es.letters`), el alfabeto `reference` de `scope` o, sin `scope`, la unión del de todos
+    /// los idiomas activos que lo tienen (`letters`) o, sin idiomas activos, el de
+    /// `DEFAULT_LANGUAGE`, o un preset.
+    fn builtin(&self, reference: &str, scope: Option<&str>) -> Option<GraphemeSet> {
+        let chars = |value: &str| GraphemeSet::from(value.chars().collect::<RangeSet>());
+
+        if let Some((lang, name)) = reference.split_once('.') {
+            return locale_alphabet(lang, name).map(|value| chars(&value));
+        }
+
+        let alphabets: Vec<String> = match scope {
+            Some(lang) => locale_alphabet(lang, reference).into_iter().collect(),
+            None if self.languages.is_empty() => locale_alphabet(DEFAULT_LANGUAGE, reference).into_iter().collect(),
+            None => self
+                .languages
+                .iter()
+                .filter_map(|(lang, _)| locale_alphabet(lang, reference))
+                .collect(),
+        };
+        if !alphabets.is_empty() {
+            return Some(chars(&alphabets.concat()));
+        }
+
+        preset(reference).map(chars)
+    }
+
+    /// Categorías no imprimibles que permite algún charset de `[charset]` o de las secciones de
+    /// los idiomas activos.
+    fn allowed(&self) -> BTreeSet<UnsafeKind> {
+        let sections = self.languages.iter().flat_map(|(_, section)| section.values());
+        self.charsets
+            .values()
+            .chain(sections)
+            .flat_map(|constraint| constraint.allow().iter().copied())
+            .collect()
+    }
+
+    /// Definiciones del charset `name`: la de `[charset]` y las de las secciones de los idiomas
+    /// activos, cada una con el idioma en el que se resuelven sus referencias.
+    fn definitions(&self, name: &str) -> Vec<(Option<&str>, &CharsetConstraint)> {
+        let global = self.charsets.get(name).map(|constraint| (None, constraint));
+        let local = self
+            .languages
+            .iter()
+            .filter_map(|(lang, section)| Some((Some(lang.as_str()), section.get(name)?)));
+        global.into_iter().chain(local).collect()
+    }
+
+    /// Nombres de todos los charsets definidos, en orden alfabético.
+    fn names(&self) -> BTreeSet<String> {
+        self.charsets
+            .keys()
+            .chain(self.languages.iter().flat_map(|(_, section)| section.keys()))
+            .cloned()
+            .collect()
+    }
+
+    /// Expande un elemento de una lista de caracteres: `@nombre` referencia un charset o un preset,
+    /// y cualquier otra cadena se interpreta con `parse_operand`. Admite expresiones de conjuntos
+    /// (`"@ascii_letters - aeiou"`).
+    /// `raw` se aplica a los caracteres de cada cadena que no es una referencia.
+    fn parse_item<F>(&self, item: &str, mut raw: F) -> std::result::Result<GraphemeSet, UtilsError>
+    where
+        F: FnMut(GraphemeSet) -> GraphemeSet,
+    {
+        evaluate_set_expression(item, |operand| match preset_reference(operand) {
+            Some(_) => self
+                .get(operand)
+                .ok_or_else(|| UtilsError::UnknownPreset(operand.to_string())),
+            None => self.parse_operand(operand).map(&mut raw),
+        })
+    }
+
+    /// Interpreta una cadena con `parse_unicode_normalized` o, con `unit = "grapheme"`, con
+    /// `parse_graphemes_normalized`.
+    fn parse_operand(&self, operand: &str) -> std::result::Result<GraphemeSet, UtilsError> {
+        match self.unit {
+            Unit::Char => parse_unicode_normalized(operand, self.normalization).map(GraphemeSet::from),
+            Unit::Grapheme => parse_graphemes_normalized(operand, self.normalization),
+        }
+    }
+
+    /// Resuelve el charset `name` siguiendo sus referencias. `path` contiene los charsets que se
+    /// están resolviendo, para detectar ciclos.
+    fn resolve(&mut self, name: &str, path: &mut Vec<String>) -> Result<GraphemeSet> {
+        if let Some(chars) = self.resolved.get(name) {
+            return Ok(chars.clone());
+        }
+
+        if let Some(start) = path.iter().position(|n| n == name) {
+            let cycle: Vec<&str> = path[start..].iter().map(String::as_str).chain([name]).collect();
+            return Err(PasswordGenError::InvalidConfig(format!(
+                "Cyclic reference between charsets: {}",
+                cycle.join(" -> ")
+            )));
+        }
+
+        path.push(name.into());
+        let definitions: Vec<(Option<String>, Vec<String>, Vec<UnsafeKind>)> = self
+            .definitions(name)
+            .into_iter()
+            .map(|(lang, constraint)| {
+                (
+                    lang.map(String::from),
+                    constraint.items().to_vec(),
+                    constraint.allow().to_vec(),
+                )
+            })
+            .collect();
+
+        let mut chars = GraphemeSet::new();
+        let mut removed = BTreeMap::new();
+        for (lang, items, allow) in &definitions {
+            for item in items {
+                let item_chars = evaluate_set_expression(item, |operand| {
+                    self.resolve_operand(name, operand, lang.as_deref(), path, allow, &mut removed)
+                })?;
+                chars = chars.union(&item_chars);
+            }
+        }
+        path.pop();
+        let warnings = removed_warnings(&format!("charset '{}'", name), &removed, Some(name));
+        self.warnings.extend(warnings);
+
+        self.resolved.insert(name.into(), chars.clone());
+        Ok(chars)
+    }
+
+    /// Expande un operando del charset `name`: una referencia `@nombre` a otro charset, a un
+    /// alfabeto de idioma o a un preset, o cualquier cadena que acepte `parse_operand`. En la
+    /// sección de un idioma (`scope`), `@nombre` se refiere al alfabeto de ese idioma. De las
+    /// cadenas se descartan los caracteres no imprimibles de categorías que no estén en `allow`
+    /// y, con `unit = "grapheme"`, los que no forman un grafema por sí solos, y se anotan en
+    /// `removed`; los charsets referenciados ya vienen filtrados con sus propias reglas.
+    fn resolve_operand(
+        &mut self,
+        name: &str,
+        operand: &str,
+        scope: Option<&str>,
+        path: &mut Vec<String>,
+        allow: &[UnsafeKind],
+        removed: &mut Removed,
+    ) -> Result<GraphemeSet> {
+        match preset_reference(operand) {
+            Some(reference) => match self.builtin(reference, scope) {
+                // Dentro de su propia definición, `@nombre` es el conjunto predefinido del mismo
+                // nombre, que así se puede ampliar o recortar (`lowercase = "@lowercase + ç"`).
+                Some(chars) if reference == name => Ok(chars),
+                _ if !self.definitions(reference).is_empty() => self.resolve(reference, path),
+                builtin => builtin.ok_or_else(|| {
+                    PasswordGenError::InvalidConfig(format!(
+                        "Charset '{}' references '{}', which is neither a charset nor a preset",
+                        name, operand
+                    ))
+                }),
+            },
+            None => {
+                let mut chars = self.parse_operand(operand).map_err(|e| {
+                    PasswordGenError::InvalidConfig(format!(
+                        "Error procesando '{}' en charset '{}': {}",
+                        operand, name, e
+                    ))
+                })?;
+                filter(&mut chars, allow, self.unit, self.normalization, removed);
+                Ok(chars)
+            }
+        }
+    }
+}
+
+/// Motivo por el que se descarta un elemento de un charset o de `include`.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Removal {
+    /// Carácter no imprimible de la categoría indicada.
+    Unsafe(UnsafeKind),
+    /// Elemento que no forma un grafema por sí solo (solo con `unit = "grapheme"`).
+    Dependent,
+    /// Elemento que cambia al normalizarlo con la forma indicada o que se combina con el
+    /// anterior.
+    Unnormalized(NormalForm),
+}
+
+/// Elementos descartados, agrupados por motivo.
+type Removed = BTreeMap<Removal, GraphemeSet>;
+
+/// Aplica a `chars` el filtro de caracteres imprimibles, descarta los elementos que no son
+/// estables bajo `form` y, con `unit = "grapheme"`, los que no forman un grafema por sí solos.
+/// Lo descartado se anota en `removed`.
+fn filter(chars: &mut GraphemeSet, allow: &[UnsafeKind], unit: Unit, form: NormalForm, removed: &mut Removed) {
+    let mut found: Removed = filter_unsafe(chars, allow)
+        .into_iter()
+        .map(|(kind, set)| (Removal::Unsafe(kind), set))
+        .collect();
+    if unit == Unit::Grapheme {
+        let dependent = remove_dependent(chars);
+        if !dependent.is_empty() {
+            found.insert(Removal::Dependent, dependent);
+        }
+    }
+    let unnormalized = remove_unnormalized(chars, form);
+    if !unnormalized.is_empty() {
+        found.insert(Removal::Unnormalized(form), unnormalized);
+    }
+
+    for (reason, set) in found {
+        let entry = removed.entry(reason).or_default();
+        *entry = entry.union(&set);
+    }
+}
+
+/// Avisos de los elementos descartados de `source`, uno por motivo. Si `charset` es `Some`, el
+/// aviso indica cómo conservar los no imprimibles con `allow`.
+fn removed_warnings(source: &str, removed: &Removed, charset: Option<&str>) -> Vec<ConfigWarning> {
+    removed
+        .iter()
+        .map(|(reason, elements)| {
+            let noun = if elements.len() == 1 { "character" } else { "characters" };
+            let (description, hint) = match (reason, charset) {
+                (Removal::Unsafe(kind), Some(name)) => (
+                    format!("{} {}", kind.name(), noun),
+                    Some(format!(
+                        "add \"{}\" to allow in charset '{}' to keep them",
+                        kind.name(),
+                        name
+                    )),
+                ),
+                (Removal::Unsafe(kind), None) => (format!("{} {}", kind.name(), noun), None),
+                (Removal::Dependent, _) if elements.len() == 1 => {
+                    ("character that does not form a grapheme on its own".into(), None)
+                }
+                (Removal::Dependent, _) => ("characters that do not form a grapheme on their own".into(), None),
+                (Removal::Unnormalized(form), _) => (
+                    format!("{} that would change under {} normalization", noun, form.name()),
+                    None,
+                ),
+            };
+            ConfigWarning::Removed {
+                source: source.into(),
+                description,
+                elements: elements.iter().collect(),
+                hint,
+            }
+        })
+        .collect()
 }
 
 impl Validator for Charset {
     fn validate(&mut self) -> Result<()> {
-        if self.charsets.is_empty() {
+        // Se resuelven en orden alfabético para que los errores sean deterministas.
+        let names = self.names();
+        if names.is_empty() {
             return Err(PasswordGenError::InvalidConfig(
                 "No character set has been specified.".into(),
             ));
         }
 
-        for (name, charset) in &self.charsets {
-            match charset {
-                CharsetConstraint::Multiple(chars) => {}
-                CharsetConstraint::One(chars) => {}
+        self.resolved.clear();
+        for name in &names {
+            if self.resolve(name, &mut Vec::new())?.is_empty() {
+                return Err(PasswordGenError::InvalidConfig(format!(
+                    "Charset '{}' does not contain any character",
+                    name
+                )));
             }
         }
 
@@ -291,9 +1385,36 @@ impl Validator for Charset {
 }
 
 /// Define restricciones para un charset particular, que puede ser un único String o una lista.
+/// La forma extendida `{ chars = [...], allow = [...] }` permite conservar categorías de
+/// caracteres no imprimibles que el filtro descarta por defecto (p. ej. `allow = ["format"]`
+/// para el ZWJ de los emojis compuestos).
 #[derive(Deserialize, Clone, Debug)]
 #[serde(untagged)]
 pub enum CharsetConstraint {
     Multiple(Vec<String>),
     One(String),
+    Detailed {
+        chars: Vec<String>,
+        #[serde(default)]
+        allow: Vec<UnsafeKind>,
+    },
+}
+
+impl CharsetConstraint {
+    /// Elementos que definen el charset.
+    fn items(&self) -> &[String] {
+        match self {
+            CharsetConstraint::Multiple(v) => v.as_slice(),
+            CharsetConstraint::One(v) => std::slice::from_ref(v),
+            CharsetConstraint::Detailed { chars, .. } => chars.as_slice(),
+        }
+    }
+
+    /// Categorías no imprimibles que el charset conserva.
+    fn allow(&self) -> &[UnsafeKind] {
+        match self {
+            CharsetConstraint::Detailed { allow, .. } => allow.as_slice(),
+            _ => &[],
+        }
+    }
 }
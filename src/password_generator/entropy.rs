@@ -0,0 +1,157 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use num_bigint::BigUint;
+use num_traits::{ToPrimitive, Zero};
+
+use super::config::{Config, Constraint, DEFAULT_ENTROPY_BITS};
+use super::error::{PasswordGenError, Result};
+use super::sampler::Sampler;
+
+/// Entropía real de las contraseñas que genera una configuración.
+///
+/// En lugar de la aproximación `log2(alfabeto^longitud)`, se cuenta exactamente cuántas
+/// contraseñas distintas cumplen el alfabeto final, el patrón, las restricciones por charset y
+/// de posición, `max-consecutive` y `max-sequence`.
+/// Como el generador elige uniformemente entre ellas, la entropía es `log2(count)`.
+#[derive(Clone, Debug)]
+pub struct Entropy {
+    /// Longitud con menos contraseñas válidas entre las que puede generar la configuración.
+    pub length: usize,
+    /// Número exacto de contraseñas válidas de esa longitud.
+    pub count: BigUint,
+    /// Entropía en bits: `log2(count)`.
+    pub bits: f64,
+}
+
+impl Entropy {
+    /// Calcula la entropía de `config`. Si `length` admite varias longitudes, se toma la más débil.
+    pub fn of(config: &Config) -> Result<Self> {
+        Prepared::new(config).map(|prepared| prepared.entropy)
+    }
+}
+
+/// Generador exacto de una configuración, con las longitudes que puede generar y su entropía.
+///
+/// Construirlo obliga a contar todas las contraseñas válidas, así que la validación de la
+/// configuración lo guarda y el generador lo reutiliza, junto con los recuentos ya hechos.
+#[derive(Clone)]
+pub(crate) struct Prepared {
+    pub sampler: Sampler,
+    pub lengths: Vec<usize>,
+    pub entropy: Entropy,
+}
+
+impl Prepared {
+    pub fn new(config: &Config) -> Result<Self> {
+        let mut sampler = Sampler::from_config(config)?;
+        let lengths = candidate_lengths(config, &mut sampler)?;
+
+        let mut weakest: Option<Entropy> = None;
+        for &length in &lengths {
+            let count = sampler.count(length)?;
+            if weakest.as_ref().is_none_or(|weakest| count < weakest.count) {
+                weakest = Some(Entropy {
+                    length,
+                    bits: bits(&count),
+                    count,
+                });
+            }
+        }
+
+        Ok(Prepared {
+            sampler,
+            lengths,
+            entropy: weakest.ok_or_else(no_valid_password)?,
+        })
+    }
+}
+
+impl fmt::Debug for Prepared {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Prepared")
+            .field("lengths", &self.lengths)
+            .field("entropy", &self.entropy)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Entropía en bits de una elección uniforme entre `count` contraseñas: `log2(count)`.
+pub(crate) fn bits(count: &BigUint) -> f64 {
+    // Se conservan los 64 bits más significativos para no perder precisión en `f64`.
+    let shift = count.bits().saturating_sub(64);
+    let top = (count >> shift).to_f64().unwrap_or(0.0);
+    top.log2() + shift as f64
+}
+
+/// Longitudes que puede generar la configuración: las de `rules.length` para las que existe
+/// alguna contraseña válida o, con `length = "auto"`, la más corta que alcanza la entropía objetivo.
+pub(crate) fn candidate_lengths(config: &Config, sampler: &mut Sampler) -> Result<Vec<usize>> {
+    let length = &config.rules.length;
+
+    let lengths: Vec<usize> = match length {
+        Constraint::Auto => {
+            let target = config.rules.min_entropy_bits.unwrap_or(DEFAULT_ENTROPY_BITS);
+            let shortest = shortest_length(sampler, target.into(), length.min()..=length.max())?.ok_or_else(|| {
+                PasswordGenError::InvalidConfig(format!(
+                    "No length up to {} reaches {} bits of entropy",
+                    length.max(),
+                    target
+                ))
+            })?;
+            vec![shortest]
+        }
+        _ => {
+            let counts = sampler.count_all(length.min()..=length.max())?;
+            let lengths: Vec<usize> = (length.min()..=length.max())
+                .zip(counts)
+                .filter(|(_, count)| !count.is_zero())
+                .map(|(length, _)| length)
+                .collect();
+            check_position_lengths(config, &lengths)?;
+            lengths
+        }
+    };
+
+    if lengths.is_empty() {
+        return Err(no_valid_password());
+    }
+
+    Ok(lengths)
+}
+
+/// Falla si alguna longitud de `rules.length` que no está en `lengths` sería posible sin las
+/// restricciones de posición: descartarla en silencio cambiaría las longitudes pedidas.
+fn check_position_lengths(config: &Config, lengths: &[usize]) -> Result<()> {
+    let length = &config.rules.length;
+    if config.get_position_rules().is_empty() || lengths.len() == length.max() - length.min() + 1 {
+        return Ok(());
+    }
+
+    let mut unconstrained = Sampler::with_positions(config, &[])?;
+    for dropped in (length.min()..=length.max()).filter(|length| !lengths.contains(length)) {
+        if !unconstrained.count(dropped)?.is_zero() {
+            return Err(PasswordGenError::InvalidConfig(format!(
+                "No password of length {} satisfies the position rules (first, last, positions)",
+                dropped
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Longitud más corta de `lengths` cuyas contraseñas válidas alcanzan `target` bits.
+fn shortest_length(sampler: &mut Sampler, target: f64, lengths: RangeInclusive<usize>) -> Result<Option<usize>> {
+    for length in lengths {
+        if bits(&sampler.count(length)?) >= target {
+            return Ok(Some(length));
+        }
+    }
+    Ok(None)
+}
+
+pub(crate) fn no_valid_password() -> PasswordGenError {
+    PasswordGenError::InvalidConfig(
+        "No password satisfies the pattern, charset and position rules for the allowed lengths".into(),
+    )
+}
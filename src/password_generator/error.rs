@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::utils::UtilsError;
+
 #[derive(Error, Debug)]
 pub enum PasswordGenError {
     #[error("An error occurred while reading the TOML file.")]
@@ -11,8 +13,29 @@ pub enum PasswordGenError {
     #[error("Invalid configuration: {0}")]
     InvalidConfig(String),
 
+    #[error("Charset '{0}' is referenced in the rules but not defined in [charset].")]
+    CharsetNotFound(String),
+
+    #[error("Invalid pattern at column {column}: expected {expected}, found {found}.")]
+    InvalidPattern {
+        column: usize,
+        expected: String,
+        found: String,
+    },
+
+    #[error("The profile only provides {bits:.2} bits of entropy, below the required {required} bits.")]
+    InsufficientEntropy { bits: f64, required: u8 },
+
     #[error("File version ({0}) is incompatible. Use the supported version ({1}) to proceed.")]
     IncompatibleVersion(String, String),
 }
 
+/// Los errores al interpretar una cadena de caracteres (rangos, clases, expresiones...) son
+/// errores de configuración.
+impl From<UtilsError> for PasswordGenError {
+    fn from(error: UtilsError) -> Self {
+        PasswordGenError::InvalidConfig(error.to_string())
+    }
+}
+
 pub type Result<T> = std::result::Result<T, PasswordGenError>;
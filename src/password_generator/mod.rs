@@ -1,22 +1,97 @@
+mod automaton;
 pub mod config;
+pub mod entropy;
 pub mod error;
+pub mod pattern;
+mod sampler;
 
 use config::Config;
+use entropy::Prepared;
+use error::Result;
+use rand::rngs::OsRng;
+use rand::{CryptoRng, RngCore, SeedableRng};
 
-pub struct GenPassword {
+pub use rand_chacha::ChaCha20Rng;
+
+/// Resultado de una generación.
+#[derive(Clone, Debug)]
+pub struct GeneratedPassword {
+    pub password: String,
+    /// Longitud elegida, en caracteres o en grafemas según `rules.unit`. Con `length = "auto"` es
+    /// la longitud calculada a partir de la entropía objetivo.
+    pub length: usize,
+    /// Entropía en bits de la elección: `log2` del número de contraseñas válidas de esa longitud.
+    pub entropy_bits: f64,
+}
+
+/// Generador de contraseñas.
+///
+/// Es genérico sobre la fuente de aleatoriedad, que debe ser criptográficamente segura
+/// (`RngCore + CryptoRng`). Por defecto usa el generador del sistema operativo (`OsRng`).
+/// Con [`GenPassword::from_seed`] o [`GenPassword::seed_from_u64`] se usa ChaCha20 con una
+/// semilla fija: la misma configuración y la misma semilla producen siempre la misma secuencia
+/// de contraseñas, de forma estable entre versiones de parche de la biblioteca. Este modo está
+/// pensado para pruebas y ficheros de referencia; una semilla conocida hace predecibles las
+/// contraseñas, así que no debe usarse para generar contraseñas reales.
+pub struct GenPassword<R = OsRng> {
+    /// El generador exacto y las longitudes posibles se guardan en la configuración al validarla;
+    /// si no se validó, se calculan en la primera llamada a `generate`.
     config: Config,
+    rng: R,
 }
 
 impl GenPassword {
     pub fn new(config: Config) -> Self {
-        GenPassword { config }
+        GenPassword::with_rng(config, OsRng)
+    }
+}
+
+impl GenPassword<ChaCha20Rng> {
+    /// Generador determinista con ChaCha20 inicializado con `seed`.
+    pub fn from_seed(config: Config, seed: [u8; 32]) -> Self {
+        GenPassword::with_rng(config, ChaCha20Rng::from_seed(seed))
     }
 
-    pub fn generate(&self) -> String {
-        let charset = self.config.get_charset().unwrap();
+    /// Generador determinista con ChaCha20 inicializado a partir de un `u64`.
+    pub fn seed_from_u64(config: Config, seed: u64) -> Self {
+        GenPassword::with_rng(config, ChaCha20Rng::seed_from_u64(seed))
+    }
+}
+
+impl<R: RngCore + CryptoRng> GenPassword<R> {
+    /// Generador que usa `rng` como fuente de aleatoriedad.
+    pub fn with_rng(config: Config, rng: R) -> Self {
+        GenPassword { config, rng }
+    }
 
-        println!("{:?}", charset);
+    /// Genera una contraseña a partir del alfabeto final de la configuración, respetando
+    /// `rules.length`, `rules.pattern` y las restricciones por charset de `[rules]`.
+    ///
+    /// Cada longitud se elige con probabilidad proporcional a su número de contraseñas válidas y
+    /// la contraseña, uniformemente entre las válidas de esa longitud: el resultado es uniforme
+    /// entre todas las contraseñas válidas de cualquier longitud permitida.
+    /// Si el patrón es más corto que `length`, el comodín `*` rellena hasta la longitud elegida.
+    /// Con `length = "auto"` se usa la longitud más corta que alcanza `min-entropy-bits`.
+    pub fn generate(&mut self) -> Result<GeneratedPassword> {
+        let prepared = match self.config.prepared.take() {
+            Some(prepared) => prepared,
+            None => Prepared::new(&self.config)?,
+        };
+        let Prepared { sampler, lengths, .. } = self.config.prepared.insert(prepared);
+        let counts = lengths
+            .iter()
+            .map(|&length| sampler.count(length))
+            .collect::<Result<Vec<_>>>()?;
+        let index = sampler::random_weighted(&mut self.rng, &counts);
+        let length = lengths[index];
+        let password = sampler
+            .sample(length, &mut self.rng)?
+            .ok_or_else(entropy::no_valid_password)?;
 
-        String::new()
+        Ok(GeneratedPassword {
+            password,
+            length,
+            entropy_bits: entropy::bits(&counts[index]),
+        })
     }
 }
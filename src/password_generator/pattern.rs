@@ -0,0 +1,380 @@
+use super::config::Unit;
+use super::error::{PasswordGenError, Result};
+
+/// Patrón ya analizado. Cada alternativa es una secuencia de elementos que describe una
+/// estructura posible de la contraseña; un patrón sin `|` tiene una única alternativa.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pattern {
+    pub alternatives: Vec<Vec<Node>>,
+}
+
+impl Pattern {
+    /// Longitud mínima de las contraseñas que describe el patrón, contada en elementos de `unit`.
+    pub fn min_length(&self, unit: Unit) -> usize {
+        alternatives_min_length(&self.alternatives, unit)
+    }
+
+    /// Longitud máxima de las contraseñas que describe el patrón, o `None` si no está acotada
+    /// (comodín o cantidades `{n,}`).
+    pub fn max_length(&self, unit: Unit) -> Option<usize> {
+        alternatives_max_length(&self.alternatives, unit)
+    }
+
+    /// Nombres de los charsets referenciados por los bloques del patrón.
+    pub fn charset_names(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        collect_charset_names(&self.alternatives, &mut names);
+        names
+    }
+
+    /// Textos literales del patrón.
+    pub fn literals(&self) -> Vec<&str> {
+        let mut literals = Vec::new();
+        collect_literals(&self.alternatives, &mut literals);
+        literals
+    }
+}
+
+fn alternatives_min_length(alternatives: &[Vec<Node>], unit: Unit) -> usize {
+    alternatives
+        .iter()
+        .map(|sequence| sequence.iter().map(|node| node.min_length(unit)).sum())
+        .min()
+        .unwrap_or(0)
+}
+
+fn alternatives_max_length(alternatives: &[Vec<Node>], unit: Unit) -> Option<usize> {
+    alternatives
+        .iter()
+        .map(|sequence| sequence.iter().map(|node| node.max_length(unit)).sum::<Option<usize>>())
+        .try_fold(0, |max, length| Some(max.max(length?)))
+}
+
+fn collect_charset_names<'a>(alternatives: &'a [Vec<Node>], names: &mut Vec<&'a str>) {
+    for node in alternatives.iter().flatten() {
+        match node {
+            Node::Block { name, .. } => names.push(name),
+            Node::Group { alternatives, .. } => collect_charset_names(alternatives, names),
+            Node::Literal(_) | Node::Wildcard => {}
+        }
+    }
+}
+
+fn collect_literals<'a>(alternatives: &'a [Vec<Node>], literals: &mut Vec<&'a str>) {
+    for node in alternatives.iter().flatten() {
+        match node {
+            Node::Literal(text) => literals.push(text),
+            Node::Group { alternatives, .. } => collect_literals(alternatives, literals),
+            Node::Block { .. } | Node::Wildcard => {}
+        }
+    }
+}
+
+/// Elemento de un patrón.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Node {
+    /// Bloque `(nombre){n,m}`: entre `n` y `m` caracteres del charset `nombre`.
+    /// Con `!` o `^` delante del nombre (`(!digits)`) el bloque usa el alfabeto final
+    /// salvo los caracteres del charset.
+    Block {
+        name: String,
+        negated: bool,
+        quantifier: Quantifier,
+    },
+    /// Grupo `(...){n,m}` con una o varias alternativas separadas por `|`, repetido entre
+    /// `n` y `m` veces. Por ejemplo `((digits){2}|(symbols))` o `((upper)(lower){2}){3}`.
+    Group {
+        alternatives: Vec<Vec<Node>>,
+        quantifier: Quantifier,
+    },
+    /// Texto literal entre comillas (`"KP-"`), que se copia tal cual en la contraseña.
+    Literal(String),
+    /// Comodín `*`: cualquier cantidad de caracteres del alfabeto final. Si hay varios, la
+    /// longitud libre se reparte entre ellos.
+    Wildcard,
+}
+
+impl Node {
+    fn min_length(&self, unit: Unit) -> usize {
+        match self {
+            Node::Block { quantifier, .. } => quantifier.min,
+            Node::Group {
+                alternatives,
+                quantifier,
+            } => quantifier
+                .min
+                .saturating_mul(alternatives_min_length(alternatives, unit)),
+            Node::Literal(text) => unit.split(text).len(),
+            Node::Wildcard => 0,
+        }
+    }
+
+    fn max_length(&self, unit: Unit) -> Option<usize> {
+        match self {
+            Node::Block { quantifier, .. } => quantifier.max,
+            Node::Group {
+                alternatives,
+                quantifier,
+            } => match alternatives_max_length(alternatives, unit)? {
+                0 => Some(0),
+                length => quantifier.max?.checked_mul(length),
+            },
+            Node::Literal(text) => Some(unit.split(text).len()),
+            Node::Wildcard => None,
+        }
+    }
+}
+
+/// Número de repeticiones de un bloque o grupo: `{n}`, `{n,}` (sin máximo) o `{n,m}`.
+/// Sin cantidad equivale a `{1}`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quantifier {
+    pub min: usize,
+    pub max: Option<usize>,
+}
+
+impl Quantifier {
+    pub const ONE: Quantifier = Quantifier { min: 1, max: Some(1) };
+}
+
+/// Analiza un patrón como `(uppercase){1}(lowercase){3}(!digits){2}*`, `"KP-"(digits){4}` o
+/// `(upper)((digits){2}|(symbols))*`.
+///
+/// Gramática:
+/// ```text
+/// patrón       := alternativas
+/// alternativas := secuencia ('|' secuencia)*
+/// secuencia    := (grupo | bloque | literal | '*')+
+/// grupo        := '(' alternativas ')' cantidad?
+/// bloque       := '(' ('!' | '^')? nombre ')' cantidad?
+/// literal      := '"' (carácter | '\"' | '\\')+ '"'
+/// cantidad     := '{' número '}' | '{' número ',' '}' | '{' número ',' número '}'
+/// ```
+/// Un paréntesis cuyo contenido empieza por `(`, `"` o `*` es un grupo; en otro caso es un bloque.
+/// Los errores indican la columna (empezando en 1) y el elemento esperado.
+pub fn parse_pattern(input: &str) -> Result<Pattern> {
+    let mut parser = Parser {
+        chars: input.chars().collect(),
+        pos: 0,
+    };
+
+    let alternatives = parser.alternatives()?;
+    if parser.peek().is_some() {
+        return Err(parser.error("'(', '\"', '*', '|' or end of pattern"));
+    }
+
+    Ok(Pattern { alternatives })
+}
+
+const EXPECTED_NODE: &str = "'(', '\"' or '*'";
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek();
+        if c.is_some() {
+            self.pos += 1;
+        }
+        c
+    }
+
+    /// Consume `expected` o falla indicando qué se esperaba.
+    fn expect(&mut self, expected: char) -> Result<()> {
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("'{}'", expected)))
+        }
+    }
+
+    /// Error en la posición actual.
+    fn error(&self, expected: &str) -> PasswordGenError {
+        self.error_at(self.pos, expected)
+    }
+
+    fn error_at(&self, pos: usize, expected: &str) -> PasswordGenError {
+        let found = match self.chars.get(pos) {
+            Some(c) => format!("'{}'", c),
+            None => "end of pattern".into(),
+        };
+
+        PasswordGenError::InvalidPattern {
+            column: pos + 1,
+            expected: expected.into(),
+            found,
+        }
+    }
+
+    /// `secuencia ('|' secuencia)*`
+    fn alternatives(&mut self) -> Result<Vec<Vec<Node>>> {
+        let mut alternatives = vec![self.sequence()?];
+
+        while self.peek() == Some('|') {
+            self.bump();
+            alternatives.push(self.sequence()?);
+        }
+
+        Ok(alternatives)
+    }
+
+    /// `(grupo | bloque | literal | '*')+`
+    fn sequence(&mut self) -> Result<Vec<Node>> {
+        let mut nodes = Vec::new();
+
+        loop {
+            match self.peek() {
+                Some('(') => nodes.push(self.parenthesized()?),
+                Some('"') => nodes.push(self.literal()?),
+                Some('*') => {
+                    self.bump();
+                    nodes.push(Node::Wildcard);
+                }
+                _ => break,
+            }
+        }
+
+        if nodes.is_empty() {
+            return Err(self.error(EXPECTED_NODE));
+        }
+
+        Ok(nodes)
+    }
+
+    /// Grupo `'(' alternativas ')' cantidad?` o bloque `'(' ('!' | '^')? nombre ')' cantidad?`.
+    fn parenthesized(&mut self) -> Result<Node> {
+        self.expect('(')?;
+
+        if matches!(self.peek(), Some('(' | '"' | '*')) {
+            let alternatives = self.alternatives()?;
+            self.expect(')')?;
+
+            return Ok(Node::Group {
+                alternatives,
+                quantifier: self.optional_quantifier()?,
+            });
+        }
+
+        let negated = matches!(self.peek(), Some('!' | '^'));
+        if negated {
+            self.bump();
+        }
+
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if matches!(c, '(' | ')' | '{' | '}' | '*' | '|') {
+                break;
+            }
+            self.bump();
+        }
+
+        let name: String = self.chars[start..self.pos].iter().collect();
+        if name.trim().is_empty() {
+            return Err(self.error("charset name"));
+        }
+        self.expect(')')?;
+
+        Ok(Node::Block {
+            name,
+            negated,
+            quantifier: self.optional_quantifier()?,
+        })
+    }
+
+    fn optional_quantifier(&mut self) -> Result<Quantifier> {
+        if self.peek() == Some('{') {
+            self.quantifier()
+        } else {
+            Ok(Quantifier::ONE)
+        }
+    }
+
+    /// `'"' texto '"'`, donde `\"` y `\\` representan una comilla y una barra invertida.
+    fn literal(&mut self) -> Result<Node> {
+        self.expect('"')?;
+
+        let mut text = String::new();
+        loop {
+            match self.peek() {
+                Some('"') => break,
+                Some('\\') => {
+                    self.bump();
+                    match self.peek() {
+                        Some(c @ ('"' | '\\')) => {
+                            self.bump();
+                            text.push(c);
+                        }
+                        _ => return Err(self.error("'\"' or '\\' after '\\'")),
+                    }
+                }
+                Some(c) => {
+                    self.bump();
+                    text.push(c);
+                }
+                None => return Err(self.error("closing '\"'")),
+            }
+        }
+
+        if text.is_empty() {
+            return Err(self.error("at least one character in the literal"));
+        }
+        self.bump();
+
+        Ok(Node::Literal(text))
+    }
+
+    /// `'{' número (',' número?)? '}'`
+    fn quantifier(&mut self) -> Result<Quantifier> {
+        self.expect('{')?;
+        let min = self.number()?;
+
+        let ranged = self.peek() == Some(',');
+        let max = if ranged {
+            self.bump();
+            if self.peek() == Some('}') {
+                None
+            } else if !self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                return Err(self.error("a number or '}'"));
+            } else {
+                let start = self.pos;
+                let max = self.number()?;
+                if max < min {
+                    return Err(self.error_at(start, &format!("a maximum greater than or equal to {}", min)));
+                }
+                Some(max)
+            }
+        } else {
+            Some(min)
+        };
+
+        if self.peek() != Some('}') {
+            let expected = if ranged { "'}'" } else { "',' or '}'" };
+            return Err(self.error(expected));
+        }
+        self.bump();
+
+        Ok(Quantifier { min, max })
+    }
+
+    fn number(&mut self) -> Result<usize> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.bump();
+        }
+
+        if start == self.pos {
+            return Err(self.error("a number"));
+        }
+
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        digits.parse().map_err(|_| self.error_at(start, "a smaller number"))
+    }
+}
@@ -0,0 +1,991 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::Hash;
+use std::iter;
+use std::ops::{Index, RangeInclusive};
+
+use num_bigint::BigUint;
+use num_traits::{ToPrimitive, Zero};
+use rand::RngCore;
+
+use super::automaton::{Dfa, Nfa};
+use super::config::{CharsetRule, Config, PositionRule};
+use super::error::{PasswordGenError, Result};
+use crate::grapheme_set::GraphemeSet;
+use crate::keyboard::{Direction, Layout};
+
+/// Número máximo de valores que se cuentan para una configuración: uno por estado y combinación
+/// de contadores por charset. Cada uno es un número grande, y `max-sequence` o `max-consecutive`
+/// junto con máximos por charset multiplican los valores: sin límite, el recuento puede tardar
+/// minutos y agotar la memoria. Los estados se recorren antes de contar nada, así que una
+/// configuración que lo supera falla enseguida.
+const MAX_COUNTED_VALUES: usize = 10_000_000;
+
+/// Error de una configuración cuyo recuento superaría `MAX_COUNTED_VALUES`.
+fn too_complex() -> PasswordGenError {
+    PasswordGenError::InvalidConfig(format!(
+        "The rules are too complex to count exactly: they need more than {} counts. Use a shorter length, fewer charset limits or a larger max-sequence",
+        MAX_COUNTED_VALUES
+    ))
+}
+
+/// Estado de la generación sin los contadores por charset: nodo del autómata del patrón,
+/// posición actual y, si hay `max-consecutive` o `max-sequence`, el átomo que representa al
+/// último carácter (ver `Sampler::lasts`), cuántas veces seguidas se ha repetido y las secuencias
+/// que terminan en él. La posición se satura pasada la última restricción de posición contada
+/// desde el principio.
+///
+/// Las completaciones de un estado se cuentan a la vez para todas las combinaciones de
+/// contadores, así que los contadores no multiplican los estados que se recorren.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct State {
+    node: usize,
+    position: usize,
+    last: Option<usize>,
+    run: usize,
+    /// Índice en `Sampler::sequences`.
+    sequences: usize,
+}
+
+/// Secuencias de dos o más caracteres que terminan en el último, con su longitud.
+type Sequences = Vec<(Step, usize)>;
+
+/// Valores distintos numerados en el orden en que aparecen.
+#[derive(Clone)]
+struct Interner<T> {
+    values: Vec<T>,
+    indices: HashMap<T, usize>,
+}
+
+impl<T: Clone + Eq + Hash> Interner<T> {
+    fn new() -> Self {
+        Interner {
+            values: Vec::new(),
+            indices: HashMap::new(),
+        }
+    }
+
+    /// Número de `value`, que se añade si no estaba.
+    fn intern(&mut self, value: T) -> usize {
+        if let Some(&index) = self.indices.get(&value) {
+            return index;
+        }
+        self.values.push(value.clone());
+        self.indices.insert(value, self.values.len() - 1);
+        self.values.len() - 1
+    }
+}
+
+impl<T> Index<usize> for Interner<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        &self.values[index]
+    }
+}
+
+/// Paso de un carácter al siguiente que continúa una secuencia para `max-sequence`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Step {
+    /// Siguiente letra del alfabeto latino (sin distinguir mayúsculas) o siguiente dígito.
+    Ascending,
+    /// Letra o dígito anterior.
+    Descending,
+    /// Tecla vecina en la dirección indicada, en la distribución número `usize`.
+    Walk(usize, Direction),
+}
+
+/// Forma de elegir el carácter dentro de un átomo.
+#[derive(Clone, Copy)]
+enum Choice {
+    /// Cualquier carácter del átomo.
+    Any,
+    /// El mismo carácter que el anterior.
+    Repeat,
+    /// Cualquier carácter del átomo salvo el anterior.
+    Different,
+}
+
+/// Grupo de caracteres del alfabeto que pertenecen exactamente a los mismos charsets con
+/// restricción, a las mismas clases del patrón y a los mismos conjuntos de las restricciones
+/// de posición. Todos los caracteres de un átomo son intercambiables frente a las reglas.
+#[derive(Clone)]
+struct Atom {
+    chars: GraphemeSet,
+    charsets: Vec<usize>,
+    classes: Vec<bool>,
+    positions: Vec<bool>,
+}
+
+/// Conjunto por el que se dividen los átomos.
+#[derive(Clone, Copy)]
+enum Split {
+    Charset,
+    Class,
+    Position,
+    /// Caracteres con la misma posición en el orden alfabético o numérico y en las
+    /// distribuciones: las secuencias dependen de esa posición y no solo del átomo.
+    Sequence,
+}
+
+/// Generador exacto de contraseñas que respetan el patrón, las restricciones por charset y de
+/// posición, `max-consecutive` y `max-sequence`.
+///
+/// Cuenta mediante programación dinámica cuántas contraseñas válidas existen a partir de cada
+/// estado y elige cada carácter con probabilidad proporcional a las contraseñas válidas que lo
+/// continúan. La salida es uniforme sobre todas las contraseñas válidas de la longitud pedida,
+/// los caracteres obligatorios quedan en posiciones aleatorias y no hace falta reintentar.
+#[derive(Clone)]
+pub(crate) struct Sampler {
+    atoms: Vec<Atom>,
+    /// Posición de cada restricción de posición, en el orden de `Atom::positions`.
+    positions: Vec<isize>,
+    /// Primera posición a partir de la que ninguna restricción contada desde el principio aplica.
+    horizon: usize,
+    max_consecutive: Option<usize>,
+    max_sequence: Option<usize>,
+    /// Pasos de secuencia entre cada par de átomos que los tienen.
+    steps: HashMap<(usize, usize), Vec<Step>>,
+    /// Cuántos pasos más, hasta `max-sequence`, puede seguir una secuencia que llega a cada átomo
+    /// con cada paso. Las que no pueden superar el máximo no se guardan en el estado.
+    reach: HashMap<(usize, Step), usize>,
+    /// Para cada átomo, los átomos cuyos movimientos cambian si el último carácter es de él: él
+    /// mismo con `max-consecutive` y los que continúan una secuencia.
+    related: Vec<Vec<usize>>,
+    /// Átomo que se guarda como último carácter en lugar de cada átomo, si hace falta guardarlo.
+    lasts: Vec<Option<usize>>,
+    dfa: Dfa,
+    /// Número de combinaciones de contadores por charset. Un charset con máximo cuenta de 0 al
+    /// máximo; uno sin máximo, de 0 al mínimo, a partir del cual todos los valores son
+    /// equivalentes.
+    combinations: usize,
+    /// Combinación a la que pasa cada combinación al añadir un carácter, o `None` si se superaría
+    /// un máximo. Los átomos de los mismos charsets comparten la tabla.
+    shifts: Vec<Vec<Option<usize>>>,
+    /// Tabla de `shifts` de cada átomo.
+    atom_shifts: Vec<usize>,
+    /// Completaciones de un estado de aceptación sin posiciones restantes: 1 en las
+    /// combinaciones que cumplen todos los mínimos y 0 en el resto.
+    complete: Vec<BigUint>,
+    /// Completaciones de un estado que no es de aceptación sin posiciones restantes.
+    incomplete: Vec<BigUint>,
+    /// Secuencias de los estados. La número 0 es la vacía.
+    sequences: Interner<Sequences>,
+    /// Contraseñas válidas que completan cada estado sin racha ni secuencias ya contado, por
+    /// posiciones restantes y combinación de contadores. El resto se calcula a partir de ellos.
+    memo: Vec<HashMap<State, Vec<BigUint>>>,
+    /// Número de valores contados hasta ahora.
+    counted: usize,
+}
+
+impl Sampler {
+    /// Construye el generador de `config`: alfabeto final, restricciones por charset y de
+    /// posición y patrón.
+    pub fn from_config(config: &Config) -> Result<Self> {
+        Sampler::with_positions(config, &config.get_position_rules())
+    }
+
+    /// Igual que `from_config`, pero con las restricciones de posición `positions` en lugar de
+    /// las de la configuración.
+    pub fn with_positions(config: &Config, positions: &[PositionRule]) -> Result<Self> {
+        let alphabet = config.get_charset()?;
+        let mut rules = config.get_charset_rules(&alphabet)?;
+        // Un máximo que la longitud no alcanza no restringe nada y solo multiplicaría las
+        // combinaciones de contadores.
+        for rule in &mut rules {
+            rule.max = rule.max.filter(|&max| max < config.rules.length.max());
+        }
+
+        let nfa = match config.rules.pattern()? {
+            Some(pattern) => Nfa::from_pattern(
+                &pattern,
+                &alphabet,
+                config.rules.unit,
+                config.rules.length.max(),
+                |name| config.get_named_charset(name, &alphabet),
+            )?,
+            None => Nfa::any(&alphabet),
+        };
+
+        let max_consecutive = config.rules.max_consecutive.map(usize::from);
+        let max_sequence = config.rules.max_sequence.map(usize::from);
+        Sampler::new(
+            &alphabet,
+            &rules,
+            positions,
+            &nfa,
+            max_consecutive,
+            max_sequence,
+            &config.rules.walk_layouts(),
+        )
+    }
+
+    pub fn new(
+        alphabet: &GraphemeSet,
+        rules: &[CharsetRule],
+        positions: &[PositionRule],
+        nfa: &Nfa,
+        max_consecutive: Option<usize>,
+        max_sequence: Option<usize>,
+        layouts: &[&Layout],
+    ) -> Result<Self> {
+        // Los literales del patrón pueden aportar caracteres que no están en el alfabeto final.
+        let universe = nfa
+            .classes
+            .iter()
+            .fold(alphabet.clone(), |universe, class| universe.union(class));
+
+        if let Some(rule) = positions
+            .iter()
+            .find(|rule| rule.chars.intersection(&universe).is_empty())
+        {
+            return Err(PasswordGenError::InvalidConfig(format!(
+                "No character of the alphabet or the pattern is allowed at position {}",
+                rule.index
+            )));
+        }
+
+        // Con `max-sequence`, las letras, dígitos y teclas de las distribuciones se agrupan por su
+        // posición en el orden alfabético o numérico y en cada distribución: los caracteres de un
+        // grupo (como `a` y `A`) forman las mismas secuencias y pueden compartir átomo.
+        let mut signatures: BTreeMap<Signature, Vec<String>> = BTreeMap::new();
+        if max_sequence.is_some() {
+            let candidates = ('0'..='9')
+                .chain('a'..='z')
+                .chain('A'..='Z')
+                .chain(layouts.iter().flat_map(|layout| layout.keys()));
+            for c in candidates {
+                let element = c.to_string();
+                let key = signature(c, layouts);
+                if key == Signature::default() || !universe.contains(&element) {
+                    continue;
+                }
+                let group = signatures.entry(key).or_default();
+                if !group.contains(&element) {
+                    group.push(element);
+                }
+            }
+        }
+        let sequence_chars: Vec<GraphemeSet> = signatures
+            .values()
+            .map(|group| group.iter().map(String::as_str).collect())
+            .collect();
+
+        // Se parte de un único átomo y se divide por cada charset, cada clase del patrón y cada
+        // restricción de posición en la parte que pertenece al conjunto y la que no. Las
+        // operaciones trabajan sobre rangos, así que el coste no depende del número de caracteres.
+        let mut atoms = vec![Atom {
+            chars: universe,
+            charsets: Vec::new(),
+            classes: Vec::new(),
+            positions: Vec::new(),
+        }];
+        let sets = rules
+            .iter()
+            .map(|rule| (&rule.chars, Split::Charset))
+            .chain(nfa.classes.iter().map(|class| (class, Split::Class)))
+            .chain(positions.iter().map(|rule| (&rule.chars, Split::Position)))
+            .chain(sequence_chars.iter().map(|set| (set, Split::Sequence)));
+
+        for (k, (set, split)) in sets.enumerate() {
+            atoms = atoms
+                .into_iter()
+                .flat_map(|atom| {
+                    [true, false].map(|inside| {
+                        let chars = if inside {
+                            atom.chars.intersection(set)
+                        } else {
+                            atom.chars.difference(set)
+                        };
+                        let mut charsets = atom.charsets.clone();
+                        let mut classes = atom.classes.clone();
+                        let mut positions = atom.positions.clone();
+                        match split {
+                            Split::Charset if inside => charsets.push(k),
+                            Split::Charset => {}
+                            Split::Class => classes.push(inside),
+                            Split::Position => positions.push(inside),
+                            Split::Sequence => {}
+                        }
+                        Atom {
+                            chars,
+                            charsets,
+                            classes,
+                            positions,
+                        }
+                    })
+                })
+                .filter(|atom| !atom.chars.is_empty())
+                .collect();
+        }
+
+        // Orden estable por primer carácter, independiente de cómo se hayan dividido.
+        atoms.sort_by_key(|atom| atom.chars.first());
+        let membership: Vec<Vec<bool>> = atoms.iter().map(|atom| atom.classes.clone()).collect();
+
+        // Todos los caracteres de un átomo comparten posición en el orden y en las distribuciones,
+        // así que basta con mirar uno.
+        let mut steps = HashMap::new();
+        if max_sequence.is_some() {
+            let representatives: Vec<(usize, char)> = atoms
+                .iter()
+                .enumerate()
+                .filter_map(|(k, atom)| Some((k, single_char(&atom.chars.first()?)?)))
+                .filter(|&(_, c)| signature(c, layouts) != Signature::default())
+                .collect();
+            for &(from, a) in &representatives {
+                for &(to, b) in &representatives {
+                    let found = sequence_steps(a, b, layouts);
+                    if !found.is_empty() {
+                        steps.insert((from, to), found);
+                    }
+                }
+            }
+        }
+
+        let reach = sequence_reach(&steps, max_sequence.unwrap_or(0));
+        let related = (0..atoms.len())
+            .map(|last| {
+                (0..atoms.len())
+                    .filter(|&atom| (max_consecutive.is_some() && atom == last) || steps.contains_key(&(last, atom)))
+                    .collect()
+            })
+            .collect();
+
+        let radices: Vec<usize> = rules.iter().map(|rule| rule.max.unwrap_or(rule.min) + 1).collect();
+        let combinations = radices
+            .iter()
+            .try_fold(1, |total: usize, &radix| total.checked_mul(radix))
+            .filter(|&total| total <= MAX_COUNTED_VALUES)
+            .ok_or_else(too_complex)?;
+        let strides: Vec<usize> = radices
+            .iter()
+            .scan(1, |stride, &radix| {
+                let current = *stride;
+                *stride *= radix;
+                Some(current)
+            })
+            .collect();
+        let digit = |combination: usize, k: usize| combination / strides[k] % radices[k];
+
+        let mut shifts = Vec::new();
+        let mut tables: HashMap<&[usize], usize> = HashMap::new();
+        let mut atom_shifts = Vec::with_capacity(atoms.len());
+        for atom in &atoms {
+            let table = *tables.entry(&atom.charsets).or_insert_with(|| {
+                let shift = (0..combinations).map(|combination| {
+                    let mut next = combination;
+                    for &k in &atom.charsets {
+                        if digit(combination, k) + 1 < radices[k] {
+                            next += strides[k];
+                        } else if rules[k].max.is_some() {
+                            return None;
+                        }
+                    }
+                    Some(next)
+                });
+                shifts.push(shift.collect());
+                shifts.len() - 1
+            });
+            atom_shifts.push(table);
+        }
+        let complete = (0..combinations)
+            .map(|combination| {
+                let met = rules
+                    .iter()
+                    .enumerate()
+                    .all(|(k, rule)| digit(combination, k) >= rule.min);
+                BigUint::from(met as u8)
+            })
+            .collect();
+
+        // Sin `max-consecutive`, el último carácter solo importa por los pasos que salen de él: los
+        // átomos con los mismos pasos hacia cada átomo se representan por el primero, y los que
+        // no tienen ninguno no se recuerdan.
+        let mut representatives: HashMap<Vec<(usize, &Vec<Step>)>, usize> = HashMap::new();
+        let lasts = (0..atoms.len())
+            .map(|atom| {
+                if max_consecutive.is_some() {
+                    return Some(atom);
+                }
+                let row: Vec<_> = (0..atoms.len())
+                    .filter_map(|to| Some((to, steps.get(&(atom, to))?)))
+                    .collect();
+                (!row.is_empty()).then(|| *representatives.entry(row).or_insert(atom))
+            })
+            .collect();
+
+        let mut sequences = Interner::new();
+        sequences.intern(Vec::new());
+
+        Ok(Sampler {
+            positions: positions.iter().map(|rule| rule.index).collect(),
+            horizon: positions
+                .iter()
+                .filter_map(|rule| usize::try_from(rule.index).ok())
+                .map(|index| index + 1)
+                .max()
+                .unwrap_or(0),
+            max_consecutive,
+            max_sequence,
+            steps,
+            reach,
+            related,
+            lasts,
+            dfa: nfa.determinize(&membership)?,
+            combinations,
+            shifts,
+            atom_shifts,
+            complete,
+            incomplete: vec![BigUint::zero(); combinations],
+            sequences,
+            memo: Vec::new(),
+            counted: 0,
+            atoms,
+        })
+    }
+
+    /// Número de contraseñas válidas de longitud `length`.
+    pub fn count(&mut self, length: usize) -> Result<BigUint> {
+        Ok(self.count_all(length..=length)?.remove(0))
+    }
+
+    /// Número de contraseñas válidas de cada longitud de `lengths`. Se cuentan todas a la vez:
+    /// una a una, cada longitud volvería a contar los estados con racha o secuencias, que no se
+    /// guardan.
+    pub fn count_all(&mut self, lengths: RangeInclusive<usize>) -> Result<Vec<BigUint>> {
+        let initial = self.initial();
+        self.count_states(lengths.clone().map(|length| (length, initial)).collect())?;
+        Ok(lengths
+            .map(|length| self.completions(length, initial, 0, &mut Cache::new()))
+            .collect())
+    }
+
+    /// Genera una contraseña de longitud `length` elegida uniformemente entre todas las
+    /// válidas. Devuelve `None` si no existe ninguna.
+    pub fn sample<R: RngCore + ?Sized>(&mut self, length: usize, rng: &mut R) -> Result<Option<String>> {
+        let mut state = self.initial();
+        let mut combination = 0;
+        let mut password = String::with_capacity(length);
+        let mut previous = None;
+        let mut cache = Cache::new();
+        self.count_states(vec![(length, state)])?;
+
+        for remaining in (1..=length).rev() {
+            let total = self.completions(remaining, state, combination, &mut cache);
+            if total.is_zero() {
+                return Ok(None);
+            }
+
+            // Se elige un índice uniforme entre todas las continuaciones válidas y se localiza
+            // el movimiento (y el carácter dentro del átomo) al que corresponde.
+            let mut target = random_below(rng, &total);
+            let mut chosen = None;
+
+            'atoms: for atom in 0..self.atoms.len() {
+                let Some(next_combination) = self.shifts[self.atom_shifts[atom]][combination] else {
+                    continue;
+                };
+                for (choice, ways, next) in self.moves(state, atom, remaining) {
+                    let completions = self.completions(remaining - 1, next, next_combination, &mut cache);
+                    let weight = &completions * ways;
+
+                    if target < weight {
+                        chosen = (target / completions)
+                            .to_usize()
+                            .and_then(|offset| self.pick(atom, choice, offset, previous.as_deref()))
+                            .map(|element| (element, next, next_combination));
+                        break 'atoms;
+                    }
+                    target -= weight;
+                }
+            }
+
+            let Some((element, next, next_combination)) = chosen else {
+                return Ok(None);
+            };
+            password.push_str(&element);
+            previous = Some(element);
+            state = next;
+            combination = next_combination;
+        }
+
+        Ok(Some(password))
+    }
+
+    fn initial(&self) -> State {
+        State {
+            node: 0,
+            position: 0,
+            last: None,
+            run: 0,
+            sequences: 0,
+        }
+    }
+
+    /// Movimientos posibles al añadir un carácter del átomo `atom` cuando quedan `remaining`
+    /// posiciones por rellenar (incluida esta): forma de elegirlo, número de caracteres que la
+    /// cumplen y estado resultante. No hay ninguno si el patrón o una restricción de posición no
+    /// admiten el átomo o el átomo alargaría una secuencia más allá de `max-sequence`. Los
+    /// máximos por charset se comprueban aparte, con `shifts`.
+    fn moves(&mut self, state: State, atom: usize, remaining: usize) -> Vec<(Choice, usize, State)> {
+        let Some(node) = self.dfa.transitions[state.node][atom] else {
+            return Vec::new();
+        };
+
+        for (k, &index) in self.positions.iter().enumerate() {
+            let applies = match usize::try_from(index) {
+                Ok(index) => state.position == index,
+                Err(_) => remaining == index.unsigned_abs(),
+            };
+            if applies && !self.atoms[atom].positions[k] {
+                return Vec::new();
+            }
+        }
+
+        // Cada paso de secuencia del último carácter a este alarga la secuencia que terminaba en
+        // el último (o empieza una de dos caracteres); el resto de secuencias se cortan.
+        let mut sequences = Vec::new();
+        if let (Some(max), Some(last)) = (self.max_sequence, state.last) {
+            for &step in self.steps.get(&(last, atom)).into_iter().flatten() {
+                let length = self.sequences[state.sequences]
+                    .iter()
+                    .find(|(previous, _)| *previous == step)
+                    .map_or(2, |(_, length)| length + 1);
+                if length > max {
+                    return Vec::new();
+                }
+                if length + self.reach.get(&(atom, step)).copied().unwrap_or(0) > max {
+                    sequences.push((step, length));
+                }
+            }
+        }
+
+        let position = (state.position + 1).min(self.horizon);
+        let size = self.atoms[atom].chars.len();
+        let last = self.lasts[atom];
+        let sequences = self.sequences.intern(sequences);
+        let next = |run| State {
+            node,
+            position,
+            last,
+            run,
+            sequences,
+        };
+
+        match self.max_consecutive {
+            None => vec![(Choice::Any, size, next(0))],
+            Some(max) if state.last == Some(atom) => {
+                let mut moves = Vec::with_capacity(2);
+                if state.run < max {
+                    moves.push((Choice::Repeat, 1, next(state.run + 1)));
+                }
+                if size > 1 {
+                    moves.push((Choice::Different, size - 1, next(1)));
+                }
+                moves
+            }
+            Some(_) => vec![(Choice::Any, size, next(1))],
+        }
+    }
+
+    /// Elemento número `offset` entre los que admite `choice` dentro de `atom`.
+    fn pick(&self, atom: usize, choice: Choice, offset: usize, previous: Option<&str>) -> Option<String> {
+        let chars = &self.atoms[atom].chars;
+
+        match choice {
+            Choice::Any => chars.nth(offset),
+            Choice::Repeat => previous.map(String::from),
+            Choice::Different => {
+                let skipped = chars.rank(previous?)?;
+                chars.nth(if offset >= skipped { offset + 1 } else { offset })
+            }
+        }
+    }
+
+    /// Cuenta las completaciones de cada estado de `roots` con sus posiciones restantes y las de
+    /// los estados a los que se llega desde ellos, salvo las que ya se conocen. Guarda en `memo`
+    /// las de los estados sin racha ni secuencias.
+    ///
+    /// Se hace de forma iterativa para que la pila no crezca con la longitud: primero se recorren,
+    /// posición a posición, los estados alcanzables que faltan, sin contar nada, y después se
+    /// cuentan desde la última posición hacia atrás. Como el recorrido ya dice cuántos valores
+    /// habrá que calcular, falla antes de empezar a contar si serían más de `MAX_COUNTED_VALUES`.
+    fn count_states(&mut self, roots: Vec<(usize, State)>) -> Result<()> {
+        let remaining = roots.iter().map(|&(remaining, _)| remaining).max().unwrap_or(0);
+        if self.memo.len() <= remaining {
+            self.memo.resize_with(remaining + 1, HashMap::new);
+        }
+
+        // `plans[r]`: estados sin contar cuando quedan `r` posiciones. `pending[r]`: estados por
+        // recorrer, y `seen[r]`, los ya recorridos o por recorrer.
+        let mut plans: Vec<Vec<Plan>> = Vec::new();
+        plans.resize_with(remaining + 1, Vec::new);
+        let mut pending: Vec<Vec<State>> = vec![Vec::new(); remaining + 1];
+        let mut seen: Vec<HashSet<State>> = vec![HashSet::new(); remaining + 1];
+        for (r, state) in roots {
+            if !self.is_counted(r, &state) && seen[r].insert(state) {
+                pending[r].push(state);
+            }
+        }
+
+        let mut values = 0;
+        for r in (1..=remaining).rev() {
+            let (above, below) = pending.split_at_mut(r);
+            let (level_pending, next_pending) = (&mut below[0], &mut above[r - 1]);
+            let (above, below) = seen.split_at_mut(r);
+            let (level_seen, next_seen) = (&mut below[0], &mut above[r - 1]);
+            while let Some(state) = level_pending.pop() {
+                let (parent, atoms) = self.parent(&state).unzip();
+                let atoms = atoms.unwrap_or_else(|| (0..self.atoms.len()).collect());
+                if let Some(parent) = parent {
+                    if !self.is_counted(r, &parent) && level_seen.insert(parent) {
+                        level_pending.push(parent);
+                    }
+                }
+                for &atom in &atoms {
+                    for from in iter::once(state).chain(parent) {
+                        for (_, _, next) in self.moves(from, atom, r) {
+                            if !self.is_counted(r - 1, &next) && next_seen.insert(next) {
+                                next_pending.push(next);
+                            }
+                        }
+                    }
+                }
+                plans[r].push(Plan { state, parent, atoms });
+            }
+
+            values += plans[r].len() * self.combinations;
+            if self.counted + values > MAX_COUNTED_VALUES {
+                return Err(too_complex());
+            }
+            seen[r] = HashSet::new();
+        }
+        self.counted += values;
+
+        // `previous`: completaciones de los estados con racha o secuencias contados en el nivel
+        // anterior, que no se guardan en `memo`.
+        let mut previous = HashMap::new();
+        for (r, mut level) in plans.into_iter().enumerate().skip(1) {
+            // Los padres se cuentan antes que los estados que dependen de ellos.
+            level.sort_by_key(|plan| match plan.parent {
+                None => 0,
+                Some(parent) if parent.last.is_none() => 1,
+                Some(_) => 2,
+            });
+
+            let mut current = HashMap::with_capacity(level.len());
+            for plan in level {
+                let mut totals = match plan.parent {
+                    Some(parent) => self.counted_at(r, &parent, &current).to_vec(),
+                    None => vec![BigUint::zero(); self.combinations],
+                };
+                // Las completaciones del padre incluyen sus movimientos por `atoms`, así que
+                // restarlos después de sumar los propios nunca deja un valor negativo.
+                for (from, add) in iter::once((plan.state, true)).chain(plan.parent.map(|parent| (parent, false))) {
+                    for &atom in &plan.atoms {
+                        for (_, ways, next) in self.moves(from, atom, r) {
+                            let shift = &self.shifts[self.atom_shifts[atom]];
+                            let next = self.counted_at(r - 1, &next, &previous);
+                            accumulate(&mut totals, next, shift, ways, add);
+                        }
+                    }
+                }
+                current.insert(plan.state, totals);
+            }
+
+            previous = HashMap::new();
+            for (state, totals) in current {
+                if self.is_plain(&state) {
+                    self.memo[r].insert(state, totals);
+                } else {
+                    previous.insert(state, totals);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Completaciones de `state` con `remaining` posiciones y la combinación de contadores
+    /// `combination`. Todos los estados a los que se llega deben estar contados con
+    /// `count_states`.
+    ///
+    /// Las de los estados con racha o secuencias no se guardan en `memo` y se calculan a partir de
+    /// su padre. Cada una puede depender de otro estado así en la posición siguiente, y así hasta
+    /// el final, de modo que se calculan con una pila propia y se guardan en `cache`, que
+    /// comparten las posiciones de una misma contraseña.
+    fn completions(&mut self, remaining: usize, state: State, combination: usize, cache: &mut Cache) -> BigUint {
+        let mut stack = vec![(remaining, state, combination)];
+        while let Some(&(r, state, combination)) = stack.last() {
+            if self.is_counted(r, &state) || cache.contains_key(&(r, state, combination)) {
+                stack.pop();
+                continue;
+            }
+
+            let terms = self.terms(r, state, combination);
+            let missing: Vec<_> = terms
+                .iter()
+                .map(|&(_, _, key)| key)
+                .filter(|&(r, state, combination)| {
+                    !self.is_counted(r, &state) && !cache.contains_key(&(r, state, combination))
+                })
+                .collect();
+            if !missing.is_empty() {
+                stack.extend(missing);
+                continue;
+            }
+
+            let (mut total, mut removed) = (BigUint::zero(), BigUint::zero());
+            for (add, ways, key) in terms {
+                let value = self.known_completions(key, cache) * ways;
+                match add {
+                    true => total += value,
+                    false => removed += value,
+                }
+            }
+            cache.insert((r, state, combination), total - removed);
+            stack.pop();
+        }
+
+        self.known_completions((remaining, state, combination), cache)
+    }
+
+    /// Términos de las completaciones de un estado que no se guarda en `memo`: las del padre,
+    /// los movimientos propios por los átomos de `parent`, que se suman, y los del padre, que se
+    /// restan. Cada término lleva el número de caracteres por los que se multiplica.
+    fn terms(&mut self, remaining: usize, state: State, combination: usize) -> Vec<(bool, usize, CacheKey)> {
+        let (parent, atoms) = self.parent(&state).unzip();
+        let atoms = atoms.unwrap_or_else(|| (0..self.atoms.len()).collect());
+        let mut terms: Vec<_> = parent
+            .map(|parent| (true, 1, (remaining, parent, combination)))
+            .into_iter()
+            .collect();
+        for (from, add) in iter::once((state, true)).chain(parent.map(|parent| (parent, false))) {
+            for &atom in &atoms {
+                let Some(next_combination) = self.shifts[self.atom_shifts[atom]][combination] else {
+                    continue;
+                };
+                for (_, ways, next) in self.moves(from, atom, remaining) {
+                    terms.push((add, ways, (remaining - 1, next, next_combination)));
+                }
+            }
+        }
+        terms
+    }
+
+    /// Completaciones de `key` ya contadas, en `memo` o en `cache`.
+    fn known_completions(&self, (remaining, state, combination): CacheKey, cache: &Cache) -> BigUint {
+        match cache.get(&(remaining, state, combination)) {
+            Some(total) => total.clone(),
+            None => self.counted_at(remaining, &state, &HashMap::new())[combination].clone(),
+        }
+    }
+
+    /// Completaciones ya contadas de `state` con `remaining` posiciones: en `memo` o, si no se
+    /// guardan ahí, en `level`.
+    fn counted_at<'a>(
+        &'a self,
+        remaining: usize,
+        state: &State,
+        level: &'a HashMap<State, Vec<BigUint>>,
+    ) -> &'a [BigUint] {
+        if remaining == 0 {
+            return match self.dfa.accepting[state.node] {
+                true => &self.complete,
+                false => &self.incomplete,
+            };
+        }
+        level
+            .get(state)
+            .or_else(|| self.memo[remaining].get(state))
+            .expect("the state is counted before the states that reach it")
+    }
+
+    /// Si las completaciones de `state` con `remaining` posiciones ya se conocen sin recorrer
+    /// más estados.
+    fn is_counted(&self, remaining: usize, state: &State) -> bool {
+        remaining == 0 || self.memo.get(remaining).is_some_and(|level| level.contains_key(state))
+    }
+
+    /// Si `state` no tiene racha ni secuencias, de modo que sus completaciones se guardan.
+    fn is_plain(&self, state: &State) -> bool {
+        state.sequences == 0 && state.run <= usize::from(self.max_consecutive.is_some())
+    }
+
+    /// Estado del que se parte para contar `state` y átomos cuyos movimientos difieren entre los
+    /// dos: las completaciones de `state` son las del padre, más sus movimientos por esos átomos,
+    /// menos los del padre por los mismos átomos.
+    ///
+    /// Un estado con racha o secuencias solo se distingue del mismo estado sin ellas en su último
+    /// átomo y en los que continúan alguna de sus secuencias. Uno sin ellas se distingue del
+    /// mismo estado sin último carácter en los átomos relacionados con el último, y se cuenta a
+    /// partir de él cuando son menos de la mitad. El resto no tiene padre.
+    fn parent(&self, state: &State) -> Option<(State, Vec<usize>)> {
+        let last = state.last?;
+        if !self.is_plain(state) {
+            let run = usize::from(self.max_consecutive.is_some());
+            let sequences = &self.sequences[state.sequences];
+            let continues = |atom: usize| {
+                self.steps
+                    .get(&(last, atom))
+                    .is_some_and(|steps| sequences.iter().any(|(step, _)| steps.contains(step)))
+            };
+            let atoms = self.related[last]
+                .iter()
+                .copied()
+                .filter(|&atom| (atom == last && state.run != run) || continues(atom))
+                .collect();
+            return Some((
+                State {
+                    run,
+                    sequences: 0,
+                    ..*state
+                },
+                atoms,
+            ));
+        }
+
+        (2 * self.related[last].len() < self.atoms.len()).then(|| {
+            let detached = State {
+                last: None,
+                run: 0,
+                sequences: 0,
+                ..*state
+            };
+            (detached, self.related[last].clone())
+        })
+    }
+}
+
+/// Posiciones restantes, estado y combinación de contadores.
+type CacheKey = (usize, State, usize);
+
+/// Completaciones de estados con racha o secuencias calculadas en `Sampler::completions`.
+type Cache = HashMap<CacheKey, BigUint>;
+
+/// Estado sin contar en `Sampler::count_states`, con su padre y los átomos por los que se cuenta
+/// (ver `Sampler::parent`).
+struct Plan {
+    state: State,
+    parent: Option<State>,
+    atoms: Vec<usize>,
+}
+
+/// Suma (o resta, si `add` es falso) a cada combinación de `totals` las completaciones de `next`
+/// en la combinación a la que pasa con `shift`, multiplicadas por `ways`.
+fn accumulate(totals: &mut [BigUint], next: &[BigUint], shift: &[Option<usize>], ways: usize, add: bool) {
+    for (total, &target) in totals.iter_mut().zip(shift) {
+        let Some(value) = target.map(|target| &next[target]) else {
+            continue;
+        };
+        match (add, ways) {
+            (_, _) if value.is_zero() => {}
+            (true, 1) => *total += value,
+            (true, _) => *total += value * ways,
+            (false, 1) => *total -= value,
+            (false, _) => *total -= value * ways,
+        }
+    }
+}
+
+/// Carácter de `element` si tiene exactamente uno.
+fn single_char(element: &str) -> Option<char> {
+    let mut chars = element.chars();
+    let c = chars.next()?;
+    chars.next().is_none().then_some(c)
+}
+
+/// Posición de un carácter en el orden alfabético o numérico y tecla en cada distribución.
+type Signature = (Option<(u8, u32)>, Vec<Option<(usize, usize)>>);
+
+/// Firma de `c`, o la vacía si no está en ningún orden ni en ninguna distribución.
+fn signature(c: char, layouts: &[&Layout]) -> Signature {
+    let keys: Vec<_> = layouts.iter().map(|layout| layout.key(c)).collect();
+    match order(c) {
+        None if keys.iter().all(Option::is_none) => Signature::default(),
+        order => (order, keys),
+    }
+}
+
+/// Tipo (dígito o letra) y posición de `c` en su orden. Las letras no distinguen mayúsculas.
+fn order(c: char) -> Option<(u8, u32)> {
+    match c {
+        '0'..='9' => Some((0, c as u32)),
+        'a'..='z' | 'A'..='Z' => Some((1, c.to_ascii_lowercase() as u32)),
+        _ => None,
+    }
+}
+
+/// Para cada átomo y paso con el que se llega a él, cuántos pasos iguales pueden seguirle como
+/// mucho, sin pasar de `max`.
+fn sequence_reach(steps: &HashMap<(usize, usize), Vec<Step>>, max: usize) -> HashMap<(usize, Step), usize> {
+    let mut reach: HashMap<(usize, Step), usize> = HashMap::new();
+    for _ in 0..max {
+        let mut next = HashMap::new();
+        for (&(from, to), found) in steps {
+            for &step in found {
+                let length = (reach.get(&(to, step)).copied().unwrap_or(0) + 1).min(max);
+                let entry = next.entry((from, step)).or_insert(0);
+                *entry = length.max(*entry);
+            }
+        }
+        reach = next;
+    }
+    reach
+}
+
+/// Pasos de secuencia de `from` a `to`: orden alfabético o numérico y recorridos en cada una de
+/// las distribuciones de `layouts`.
+fn sequence_steps(from: char, to: char, layouts: &[&Layout]) -> Vec<Step> {
+    // Una letra y un dígito nunca son seguidos.
+    let mut steps = Vec::new();
+    if let (Some((from_kind, a)), Some((to_kind, b))) = (order(from), order(to)) {
+        if from_kind == to_kind && b == a + 1 {
+            steps.push(Step::Ascending);
+        } else if from_kind == to_kind && a == b + 1 {
+            steps.push(Step::Descending);
+        }
+    }
+    for (k, layout) in layouts.iter().enumerate() {
+        if let Some(direction) = layout.walk(from, to) {
+            steps.push(Step::Walk(k, direction));
+        }
+    }
+
+    steps
+}
+
+/// Índice de `weights` elegido con probabilidad proporcional a su peso. La suma de los pesos
+/// debe ser mayor que 0.
+pub(crate) fn random_weighted<R: RngCore + ?Sized>(rng: &mut R, weights: &[BigUint]) -> usize {
+    let mut target = random_below(rng, &weights.iter().sum());
+    for (index, weight) in weights.iter().enumerate() {
+        if &target < weight {
+            return index;
+        }
+        target -= weight;
+    }
+    weights.len() - 1
+}
+
+/// Entero uniforme en `[0, bound)` por rechazo, sin sesgo de módulo. `bound` debe ser mayor que 0.
+///
+/// Solo consume bytes de `rng.fill_bytes`, así que el resultado para una semilla dada no depende
+/// de los algoritmos de muestreo de `rand`, que pueden cambiar entre versiones.
+fn random_below<R: RngCore + ?Sized>(rng: &mut R, bound: &BigUint) -> BigUint {
+    let bits = bound.bits();
+    let mut bytes = vec![0u8; bits.div_ceil(8) as usize];
+    let excess = bytes.len() as u64 * 8 - bits;
+
+    loop {
+        rng.fill_bytes(&mut bytes);
+        if let Some(last) = bytes.last_mut() {
+            *last &= 0xFF >> excess;
+        }
+
+        let value = BigUint::from_bytes_le(&bytes);
+        if &value < bound {
+            return value;
+        }
+    }
+}
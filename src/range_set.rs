@@ -0,0 +1,310 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+/// Número de sustitutos (`U+D800-U+DFFF`), que no son `char` válidos.
+const SURROGATES: u32 = 0x800;
+
+/// Número total de `char` válidos.
+const SCALAR_VALUES: u32 = char::MAX as u32 + 1 - SURROGATES;
+
+/// Conjunto de caracteres Unicode representado como lista de inversión: una lista ordenada de
+/// rangos disjuntos. Ocupa memoria proporcional al número de rangos y no al de caracteres, así
+/// que `U+0000-U+10FFFF` es un único rango.
+///
+/// La pertenencia se comprueba en `O(log n)`; la unión, la diferencia y la intersección se
+/// calculan en tiempo lineal en el número de rangos, y `nth` permite elegir un carácter
+/// uniformemente a partir de un índice.
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct RangeSet {
+    /// Rangos semiabiertos `[inicio, fin)` sobre índices densos (ver `index`), ordenados,
+    /// disjuntos y nunca contiguos. Al saltar los sustitutos, los índices no tienen huecos y
+    /// la longitud de un rango es exactamente su número de caracteres.
+    ranges: Vec<(u32, u32)>,
+}
+
+/// Índice denso de `c`: los caracteres posteriores a los sustitutos se desplazan para cerrar
+/// el hueco.
+fn index(c: char) -> u32 {
+    let code = c as u32;
+    if code >= 0xE000 {
+        code - SURROGATES
+    } else {
+        code
+    }
+}
+
+/// Carácter con índice denso `i`. `i` debe ser menor que `SCALAR_VALUES`.
+fn char_at(i: u32) -> char {
+    let code = if i >= 0xD800 { i + SURROGATES } else { i };
+    char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+}
+
+impl RangeSet {
+    /// Conjunto vacío.
+    pub fn new() -> Self {
+        RangeSet::default()
+    }
+
+    /// Todos los caracteres Unicode.
+    pub fn full() -> Self {
+        RangeSet {
+            ranges: vec![(0, SCALAR_VALUES)],
+        }
+    }
+
+    /// Caracteres entre `start` y `end`, ambos incluidos. Vacío si `start > end`.
+    pub fn from_range(start: char, end: char) -> Self {
+        if start > end {
+            return RangeSet::new();
+        }
+
+        RangeSet {
+            ranges: vec![(index(start), index(end) + 1)],
+        }
+    }
+
+    /// Conjunto formado por varios rangos `(inicio, fin)` inclusivos, en cualquier orden.
+    pub fn from_ranges<I: IntoIterator<Item = (char, char)>>(ranges: I) -> Self {
+        let mut ranges: Vec<(u32, u32)> = ranges
+            .into_iter()
+            .filter(|(start, end)| start <= end)
+            .map(|(start, end)| (index(start), index(end) + 1))
+            .collect();
+        ranges.sort_unstable();
+
+        let mut merged: Vec<(u32, u32)> = Vec::with_capacity(ranges.len());
+        for (start, end) in ranges {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+
+        RangeSet { ranges: merged }
+    }
+
+    /// Número de caracteres del conjunto.
+    pub fn len(&self) -> usize {
+        self.ranges.iter().map(|(start, end)| (end - start) as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Indica si `c` pertenece al conjunto, en `O(log n)`.
+    pub fn contains(&self, c: char) -> bool {
+        self.find(index(c)).is_ok()
+    }
+
+    /// Carácter número `n` del conjunto en orden de código, o `None` si `n >= len()`.
+    pub fn nth(&self, mut n: usize) -> Option<char> {
+        for &(start, end) in &self.ranges {
+            let size = (end - start) as usize;
+            if n < size {
+                return Some(char_at(start + n as u32));
+            }
+            n -= size;
+        }
+        None
+    }
+
+    /// Posición de `c` dentro del conjunto (inversa de `nth`), o `None` si no pertenece.
+    pub fn rank(&self, c: char) -> Option<usize> {
+        let i = index(c);
+        let range = self.find(i).ok()?;
+        let before: usize = self.ranges[..range]
+            .iter()
+            .map(|(start, end)| (end - start) as usize)
+            .sum();
+        Some(before + (i - self.ranges[range].0) as usize)
+    }
+
+    /// Primer carácter del conjunto.
+    pub fn first(&self) -> Option<char> {
+        self.ranges.first().map(|&(start, _)| char_at(start))
+    }
+
+    /// Caracteres del conjunto en orden de código.
+    pub fn iter(&self) -> impl Iterator<Item = char> + '_ {
+        self.ranges.iter().flat_map(|&(start, end)| (start..end).map(char_at))
+    }
+
+    /// Rangos del conjunto como pares `(inicio, fin)` inclusivos.
+    pub fn ranges(&self) -> impl Iterator<Item = (char, char)> + '_ {
+        self.ranges
+            .iter()
+            .map(|&(start, end)| (char_at(start), char_at(end - 1)))
+    }
+
+    pub fn union(&self, other: &RangeSet) -> RangeSet {
+        self.combine(other, |a, b| a || b)
+    }
+
+    pub fn difference(&self, other: &RangeSet) -> RangeSet {
+        self.combine(other, |a, b| a && !b)
+    }
+
+    pub fn intersection(&self, other: &RangeSet) -> RangeSet {
+        self.combine(other, |a, b| a && b)
+    }
+
+    /// Todos los caracteres Unicode que no están en el conjunto.
+    pub fn complement(&self) -> RangeSet {
+        RangeSet::full().difference(self)
+    }
+
+    /// Índice del rango que contiene el índice denso `i`, o dónde se insertaría.
+    fn find(&self, i: u32) -> Result<usize, usize> {
+        self.ranges.binary_search_by(|&(start, end)| {
+            if end <= i {
+                Ordering::Less
+            } else if start > i {
+                Ordering::Greater
+            } else {
+                Ordering::Equal
+            }
+        })
+    }
+
+    /// Recorre los extremos de ambos conjuntos y conserva los tramos en los que `keep` es cierto.
+    fn combine<F: Fn(bool, bool) -> bool>(&self, other: &RangeSet, keep: F) -> RangeSet {
+        // Los extremos de cada conjunto ya están ordenados: basta mezclarlos.
+        let mut left = self.ranges.iter().flat_map(|&(start, end)| [start, end]).peekable();
+        let mut right = other.ranges.iter().flat_map(|&(start, end)| [start, end]).peekable();
+        let mut bounds: Vec<u32> = Vec::with_capacity(2 * (self.ranges.len() + other.ranges.len()));
+        loop {
+            let next = match (left.peek(), right.peek()) {
+                (Some(&x), Some(&y)) => match x.cmp(&y) {
+                    Ordering::Less => left.next(),
+                    Ordering::Greater => right.next(),
+                    Ordering::Equal => {
+                        right.next();
+                        left.next()
+                    }
+                },
+                (Some(_), None) => left.next(),
+                (None, _) => right.next(),
+            };
+            match next {
+                Some(bound) => bounds.push(bound),
+                None => break,
+            }
+        }
+
+        let (mut a, mut b) = (0, 0);
+        let mut ranges: Vec<(u32, u32)> = Vec::new();
+        for window in bounds.windows(2) {
+            let (start, end) = (window[0], window[1]);
+            while a < self.ranges.len() && self.ranges[a].1 <= start {
+                a += 1;
+            }
+            while b < other.ranges.len() && other.ranges[b].1 <= start {
+                b += 1;
+            }
+
+            let in_a = a < self.ranges.len() && self.ranges[a].0 <= start;
+            let in_b = b < other.ranges.len() && other.ranges[b].0 <= start;
+            if keep(in_a, in_b) {
+                match ranges.last_mut() {
+                    Some(last) if last.1 == start => last.1 = end,
+                    _ => ranges.push((start, end)),
+                }
+            }
+        }
+
+        RangeSet { ranges }
+    }
+}
+
+impl From<char> for RangeSet {
+    fn from(c: char) -> Self {
+        RangeSet::from_range(c, c)
+    }
+}
+
+impl FromIterator<char> for RangeSet {
+    fn from_iter<I: IntoIterator<Item = char>>(chars: I) -> Self {
+        RangeSet::from_ranges(chars.into_iter().map(|c| (c, c)))
+    }
+}
+
+impl fmt::Debug for RangeSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.ranges().map(|(start, end)| start..=end))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranges(set: &RangeSet) -> Vec<(char, char)> {
+        set.ranges().collect()
+    }
+
+    #[test]
+    fn adjacent_ranges_are_merged() {
+        let a = RangeSet::from_range('a', 'c');
+        let b = RangeSet::from_range('d', 'f');
+        assert_eq!(ranges(&a.union(&b)), [('a', 'f')]);
+        assert_eq!(ranges(&RangeSet::from_ranges([('d', 'f'), ('a', 'c')])), [('a', 'f')]);
+        assert!(a.intersection(&b).is_empty());
+        assert_eq!(a.difference(&b), a);
+    }
+
+    #[test]
+    fn overlapping_ranges_are_combined() {
+        let a = RangeSet::from_range('a', 'm');
+        let b = RangeSet::from_range('h', 'z');
+        assert_eq!(ranges(&a.union(&b)), [('a', 'z')]);
+        assert_eq!(ranges(&a.intersection(&b)), [('h', 'm')]);
+        assert_eq!(ranges(&a.difference(&b)), [('a', 'g')]);
+        assert_eq!(ranges(&b.difference(&a)), [('n', 'z')]);
+
+        // Una diferencia en medio de un rango lo parte en dos.
+        let hole = RangeSet::from_range('a', 'z').difference(&RangeSet::from_range('f', 'k'));
+        assert_eq!(ranges(&hole), [('a', 'e'), ('l', 'z')]);
+        assert_eq!(hole.len(), 20);
+    }
+
+    #[test]
+    fn nth_and_rank_are_inverses() {
+        let set = RangeSet::from_ranges([('0', '9'), ('a', 'f'), ('\u{1F600}', '\u{1F64F}')]);
+        assert_eq!(set.len(), 10 + 6 + 80);
+        for n in 0..set.len() {
+            let c = set.nth(n).unwrap();
+            assert_eq!(set.rank(c), Some(n), "{:?}", c);
+        }
+        assert_eq!(set.nth(set.len()), None);
+        assert_eq!(set.rank('g'), None);
+        assert_eq!(
+            set.iter().collect::<Vec<_>>(),
+            (0..set.len()).map(|n| set.nth(n).unwrap()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn ranges_skip_the_surrogates() {
+        let set = RangeSet::from_range('\u{D7FE}', '\u{E001}');
+        assert_eq!(set.len(), 4);
+        assert_eq!(
+            set.iter().collect::<Vec<_>>(),
+            ['\u{D7FE}', '\u{D7FF}', '\u{E000}', '\u{E001}']
+        );
+        assert_eq!(set.nth(2), Some('\u{E000}'));
+        assert_eq!(set.rank('\u{E001}'), Some(3));
+        assert_eq!(ranges(&set), [('\u{D7FE}', '\u{E001}')]);
+
+        // Los caracteres a ambos lados del hueco son contiguos.
+        let joined = RangeSet::from('\u{D7FF}').union(&RangeSet::from('\u{E000}'));
+        assert_eq!(ranges(&joined), [('\u{D7FF}', '\u{E000}')]);
+        let split = set.difference(&RangeSet::from('\u{E000}'));
+        assert_eq!(ranges(&split), [('\u{D7FE}', '\u{D7FF}'), ('\u{E001}', '\u{E001}')]);
+
+        assert_eq!(RangeSet::full().len(), 0x110000 - 0x800);
+        assert!(RangeSet::full().complement().is_empty());
+    }
+}
@@ -1,6 +1,19 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::OnceLock;
+
+use regex_syntax::hir::{Class, HirKind};
+use serde::Deserialize;
 use thiserror::Error;
+use unicode_blocks::{find_unicode_block, UnicodeBlock};
+use unicode_normalization::char::canonical_combining_class;
+use unicode_normalization::{is_nfc_quick, is_nfkc_quick, IsNormalized, UnicodeNormalization};
+use unicode_security::skeleton;
 use unicode_segmentation::UnicodeSegmentation;
 
+use crate::grapheme_set::GraphemeSet;
+use crate::range_set::RangeSet;
+
 #[derive(Error, Debug)]
 pub enum UtilsError {
     #[error("Formato inválido: {0}")]
@@ -11,12 +24,63 @@ pub enum UtilsError {
 
     #[error("Código Unicode inválido: {0}")]
     InvalidUnicodeCode(String),
+
+    #[error("Preset desconocido: {0}")]
+    UnknownPreset(String),
+
+    #[error("Idioma desconocido: {0}")]
+    UnknownLanguage(String),
+
+    #[error("Propiedad Unicode desconocida: {0}")]
+    UnknownProperty(String),
+
+    #[error("Bloque Unicode desconocido: {0}")]
+    UnknownBlock(String),
+
+    #[error("Clase inválida '{input}' en la columna {column}: {reason}")]
+    InvalidClass {
+        input: String,
+        column: usize,
+        reason: String,
+    },
+
+    #[error("Expresión de conjuntos inválida '{input}': {reason}")]
+    InvalidExpression { input: String, reason: String },
 }
 
 pub type Result<T> = std::result::Result<T, UtilsError>;
 
-/// Función principal para parsear rangos o cadenas.
-pub fn parse_unicode(input: &str) -> Result<Vec<char>> {
+/// Función principal para parsear rangos, presets, propiedades Unicode o cadenas.
+pub fn parse_unicode(input: &str) -> Result<RangeSet> {
+    // 0. Referencia a un preset: "@digits", "@ascii_letters", etc., o al alfabeto de un idioma:
+    //    "@es.letters".
+    if let Some(name) = preset_reference(input) {
+        if let Some((lang, name)) = name.split_once('.') {
+            if !is_known_language(lang) {
+                return Err(UtilsError::UnknownLanguage(lang.to_string()));
+            }
+            return locale_alphabet(lang, name)
+                .ok_or_else(|| UtilsError::UnknownPreset(input.to_string()))
+                .map(|value| value.chars().collect());
+        }
+        return preset(name)
+            .map(|value| value.chars().collect())
+            .ok_or_else(|| UtilsError::UnknownPreset(input.to_string()));
+    }
+
+    // Selectores de propiedades Unicode: "\p{Script=Cyrillic}", "\p{Lu}", "block:Hiragana".
+    if is_property_selector(input) {
+        return parse_property(input);
+    }
+    if let Some(name) = input.strip_prefix("block:") {
+        return parse_block(name);
+    }
+
+    // Clase entre corchetes: "[a-zA-Z0-9_]", "[^\"']".
+    if is_bracket_class(input) {
+        return parse_bracket_class(input);
+    }
+
     // 1. Si contiene un guion (-), podrían ser 2 casos:
     //    a) Rango de Unicode: U+XXXX-U+YYYY
     //    b) Rango simple (como "a-z" o "0-9"), pero solo si ambos lados tienen exactamente 1 grafema
@@ -24,13 +88,13 @@ pub fn parse_unicode(input: &str) -> Result<Vec<char>> {
     if input.contains('-') {
         // Intentar parsear rango Unicode "U+...-U+..."
         if is_unicode_hex_range(input) {
-            return parse_unicode_hex_range(input);
+            parse_unicode_hex_range(input)
         } else if is_single_grapheme_range(input) {
             // Rango tipo "a-z", "0-9", "Ñ-ß", etc.
-            return parse_single_char_range(input);
+            parse_single_char_range(input)
         } else {
             // No cumple con las reglas de rango => tomar como literal
-            return Ok(input.chars().collect());
+            Ok(literal_text(input).chars().collect())
         }
     } else {
         // 2. No contiene guion:
@@ -41,25 +105,409 @@ pub fn parse_unicode(input: &str) -> Result<Vec<char>> {
             // Parsear algo tipo "U+1F64F"
             let code = parse_single_unicode_hex(input)?;
             let ch = char::from_u32(code).ok_or(UtilsError::InvalidUnicodeCode(input.to_string()))?;
-            return Ok(vec![ch]);
+            Ok(RangeSet::from(ch))
         } else {
-            // Devolver cada char/grafema como un char
-            // (si prefieres separar por grafemas, lo hacemos con graphemes)
-            // Ej. "ABC" => ['A','B','C'], "Ñ" => ['Ñ']
-            // Si quisieras tratar grafemas compuestos, deberías recoger graphemes.
-            let mut chars = Vec::new();
-            for g in input.graphemes(true) {
-                // OJO: un grapheme puede tener > 1 char real
-                // Si deseas unirlos, tendrías que decidir cómo.
-                // Aquí simplificamos tomando el primer char del grapheme.
-                // O, si quieres cada "grapheme" como un solo `char`,
-                // lamentablemente en Rust no existe un `char` que contenga
-                // un grapheme multicodepoint. Toca convertirlo a String.
-                for c in g.chars() {
-                    chars.push(c);
+            // Cada char por separado: "ABC" => ['A','B','C']. Un grafema compuesto como "🇪🇸"
+            // se divide en sus caracteres; para conservarlo entero está `parse_graphemes`.
+            Ok(literal_text(input).chars().collect())
+        }
+    }
+}
+
+/// Como `parse_unicode`, pero los literales se dividen en grafemas extendidos en lugar de en
+/// caracteres: `"🇪🇸👨‍👩‍👧"` son dos elementos. El resto de formas (rangos, propiedades,
+/// presets...) producen grafemas de un solo carácter.
+pub fn parse_graphemes(input: &str) -> Result<GraphemeSet> {
+    if is_literal(input) {
+        Ok(literal_text(input).graphemes(true).collect())
+    } else {
+        parse_unicode(input).map(GraphemeSet::from)
+    }
+}
+
+/// Como `parse_unicode`, pero un literal se normaliza con `form` antes de dividirlo, de modo que
+/// `"é"` escrito como `e` y `U+0301` produce `U+00E9`. El resto de formas no se modifican: un
+/// carácter que no sea estable bajo `form` se descarta después con `remove_unnormalized`.
+pub fn parse_unicode_normalized(input: &str, form: NormalForm) -> Result<RangeSet> {
+    if is_literal(input) {
+        Ok(form.normalize(literal_text(input)).chars().collect())
+    } else {
+        parse_unicode(input)
+    }
+}
+
+/// Como `parse_graphemes`, pero normalizando los literales con `form` (ver
+/// `parse_unicode_normalized`).
+pub fn parse_graphemes_normalized(input: &str, form: NormalForm) -> Result<GraphemeSet> {
+    if is_literal(input) {
+        Ok(form.normalize(literal_text(input)).graphemes(true).collect())
+    } else {
+        parse_unicode(input).map(GraphemeSet::from)
+    }
+}
+
+/// Indica si `parse_unicode` trata `input` como una cadena literal.
+fn is_literal(input: &str) -> bool {
+    !(preset_reference(input).is_some()
+        || is_property_selector(input)
+        || input.starts_with("block:")
+        || is_bracket_class(input)
+        || is_single_unicode_hex(input)
+        || (input.contains('-') && (is_unicode_hex_range(input) || is_single_grapheme_range(input))))
+}
+
+/// Texto de una cadena literal. Una barra invertida delante de `@` al principio se quita: así
+/// se escriben literales que, sin ella, serían una referencia (`"\@abc"` son `@`, `a`, `b` y `c`).
+fn literal_text(input: &str) -> &str {
+    if input.starts_with("\\@") {
+        &input[1..]
+    } else {
+        input
+    }
+}
+
+/// Presets reservados: conjuntos de caracteres predefinidos que se referencian con `@nombre`.
+const PRESETS: &[(&str, &str)] = &[
+    ("ascii_lowercase", "abcdefghijklmnopqrstuvwxyz"),
+    ("ascii_uppercase", "ABCDEFGHIJKLMNOPQRSTUVWXYZ"),
+    ("ascii_letters", "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ"),
+    ("digits", "0123456789"),
+    ("hexdigits", "0123456789abcdefABCDEF"),
+    ("hex_lower", "0123456789abcdef"),
+    ("hex_upper", "0123456789ABCDEF"),
+    ("octdigits", "01234567"),
+    ("punctuation", r##"!"#$%&'()*+,-./:;<=>?@[\]^_`{|}~"##),
+    (
+        "printable",
+        r##"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ!"#$%&'()*+,-./:;<=>?@[\]^_`{|}~"##,
+    ),
+    // RFC 4648, sección 6.
+    ("base32", "ABCDEFGHIJKLMNOPQRSTUVWXYZ234567"),
+    // Alfabeto de Bitcoin: sin 0, O, I ni l.
+    ("base58", "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz"),
+    // Caracteres no reservados de RFC 3986: no necesitan codificarse en una URL.
+    (
+        "url_safe",
+        "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-._~",
+    ),
+    // Caracteres que no necesitan comillas en un shell POSIX.
+    (
+        "shell_safe",
+        "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789_@%+=:,./-",
+    ),
+];
+
+/// Caracteres del preset `name` (sin `@`), o `None` si no existe.
+pub fn preset(name: &str) -> Option<&'static str> {
+    PRESETS
+        .iter()
+        .find(|(preset, _)| *preset == name)
+        .map(|(_, value)| *value)
+}
+
+/// Si `input` es una referencia a preset (`@` seguido de letras, dígitos o `_`), devuelve el nombre.
+/// El nombre puede llevar un punto para referenciar el alfabeto de un idioma (`@es.letters`).
+/// Cualquier otra cadena que empiece por `@` (p. ej. `"@"` o `"@#!"`) se trata como literal, igual
+/// que las que empiezan por `\@` (ver `literal_text`).
+pub fn preset_reference(input: &str) -> Option<&str> {
+    let name = input.strip_prefix('@')?;
+    let word = |part: &str| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    let valid = match name.split_once('.') {
+        Some((lang, alphabet)) => word(lang) && word(alphabet),
+        None => word(name),
+    };
+    valid.then_some(name)
+}
+
+/// Alfabetos de cada idioma (código ISO 639-1): minúsculas y mayúsculas, incluidas las letras
+/// acentuadas de uso habitual.
+const LOCALE_ALPHABETS: &[(&str, &str, &str)] = &[
+    ("en", "abcdefghijklmnopqrstuvwxyz", "ABCDEFGHIJKLMNOPQRSTUVWXYZ"),
+    (
+        "es",
+        "abcdefghijklmnopqrstuvwxyzáéíñóúü",
+        "ABCDEFGHIJKLMNOPQRSTUVWXYZÁÉÍÑÓÚÜ",
+    ),
+    (
+        "ca",
+        "abcdefghijklmnopqrstuvwxyzàçèéíïòóúü",
+        "ABCDEFGHIJKLMNOPQRSTUVWXYZÀÇÈÉÍÏÒÓÚÜ",
+    ),
+    (
+        "pt",
+        "abcdefghijklmnopqrstuvwxyzàáâãçéêíóôõú",
+        "ABCDEFGHIJKLMNOPQRSTUVWXYZÀÁÂÃÇÉÊÍÓÔÕÚ",
+    ),
+    (
+        "fr",
+        "abcdefghijklmnopqrstuvwxyzàâæçèéêëîïôùûüÿœ",
+        "ABCDEFGHIJKLMNOPQRSTUVWXYZÀÂÆÇÈÉÊËÎÏÔÙÛÜŸŒ",
+    ),
+    (
+        "it",
+        "abcdefghijklmnopqrstuvwxyzàèéìîòóù",
+        "ABCDEFGHIJKLMNOPQRSTUVWXYZÀÈÉÌÎÒÓÙ",
+    ),
+    ("de", "abcdefghijklmnopqrstuvwxyzßäöü", "ABCDEFGHIJKLMNOPQRSTUVWXYZẞÄÖÜ"),
+    (
+        "nl",
+        "abcdefghijklmnopqrstuvwxyzáèéëïóöü",
+        "ABCDEFGHIJKLMNOPQRSTUVWXYZÁÈÉËÏÓÖÜ",
+    ),
+    ("sv", "abcdefghijklmnopqrstuvwxyzäåö", "ABCDEFGHIJKLMNOPQRSTUVWXYZÄÅÖ"),
+    ("fi", "abcdefghijklmnopqrstuvwxyzäåö", "ABCDEFGHIJKLMNOPQRSTUVWXYZÄÅÖ"),
+    ("da", "abcdefghijklmnopqrstuvwxyzåæø", "ABCDEFGHIJKLMNOPQRSTUVWXYZÅÆØ"),
+    ("no", "abcdefghijklmnopqrstuvwxyzåæø", "ABCDEFGHIJKLMNOPQRSTUVWXYZÅÆØ"),
+    (
+        "pl",
+        "abcdefghijklmnopqrstuvwxyzóąćęłńśźż",
+        "ABCDEFGHIJKLMNOPQRSTUVWXYZÓĄĆĘŁŃŚŹŻ",
+    ),
+    (
+        "cs",
+        "abcdefghijklmnopqrstuvwxyzáéíóúýčďěňřšťůž",
+        "ABCDEFGHIJKLMNOPQRSTUVWXYZÁÉÍÓÚÝČĎĚŇŘŠŤŮŽ",
+    ),
+    // Sin q, w ni x, y con la i sin punto y la I con punto.
+    ("tr", "abcçdefgğhıijklmnoöprsştuüvyz", "ABCÇDEFGĞHIİJKLMNOÖPRSŞTUÜVYZ"),
+    (
+        "ru",
+        "абвгдеёжзийклмнопрстуфхцчшщъыьэюя",
+        "АБВГДЕЁЖЗИЙКЛМНОПРСТУФХЦЧШЩЪЫЬЭЮЯ",
+    ),
+    (
+        "uk",
+        "абвгґдеєжзиіїйклмнопрстуфхцчшщьюя",
+        "АБВГҐДЕЄЖЗИІЇЙКЛМНОПРСТУФХЦЧШЩЬЮЯ",
+    ),
+    (
+        "el",
+        "αβγδεζηθικλμνξοπρστυφχψωάέήίόύώϊϋΐΰς",
+        "ΑΒΓΔΕΖΗΘΙΚΛΜΝΞΟΠΡΣΤΥΦΧΨΩΆΈΉΊΌΎΏΪΫ",
+    ),
+];
+
+/// Indica si hay alfabetos predefinidos para el idioma `lang`.
+pub fn is_known_language(lang: &str) -> bool {
+    LOCALE_ALPHABETS.iter().any(|(code, _, _)| *code == lang)
+}
+
+/// Caracteres del alfabeto `name` (`lowercase`, `uppercase` o `letters`) del idioma `lang`, o
+/// `None` si no existe.
+pub fn locale_alphabet(lang: &str, name: &str) -> Option<String> {
+    let (_, lower, upper) = LOCALE_ALPHABETS.iter().find(|(code, _, _)| *code == lang)?;
+    match name {
+        "lowercase" => Some(lower.to_string()),
+        "uppercase" => Some(upper.to_string()),
+        "letters" => Some(format!("{}{}", lower, upper)),
+        _ => None,
+    }
+}
+
+/// Verifica si la cadena es un selector `\p{...}` o `\P{...}` (negado).
+fn is_property_selector(input: &str) -> bool {
+    (input.starts_with("\\p{") || input.starts_with("\\P{")) && input.find('}') == Some(input.len() - 1)
+}
+
+/// Expande un selector de propiedad Unicode con las tablas de `regex-syntax`: scripts
+/// (`\p{Script=Greek}`, `\p{sc=Cyrl}`, `\p{Greek}`), categorías generales (`\p{Lu}`,
+/// `\p{gc=Nd}`) y el resto de propiedades que admite la sintaxis de `regex`.
+fn parse_property(input: &str) -> Result<RangeSet> {
+    property_set(input).ok_or_else(|| UtilsError::UnknownProperty(input.to_string()))
+}
+
+/// Caracteres de un selector `\p{...}`, o `None` si no es válido.
+fn property_set(selector: &str) -> Option<RangeSet> {
+    match regex_syntax::Parser::new().parse(selector).ok()?.into_kind() {
+        HirKind::Class(Class::Unicode(class)) => Some(RangeSet::from_ranges(
+            class.ranges().iter().map(|range| (range.start(), range.end())),
+        )),
+        _ => None,
+    }
+}
+
+/// Expande `block:Nombre` a los caracteres asignados del bloque Unicode `Nombre`. El nombre se
+/// compara sin distinguir mayúsculas, espacios, guiones ni `_` (`block:Latin_1_Supplement`).
+fn parse_block(name: &str) -> Result<RangeSet> {
+    let wanted = loose_name(name);
+    let block = unicode_blocks()
+        .find(|block| loose_name(block.name()) == wanted)
+        .ok_or_else(|| UtilsError::UnknownBlock(name.to_string()))?;
+
+    let unassigned = property_set("\\p{Cn}").unwrap_or_default();
+    Ok(code_range(block.start(), block.end()).difference(&unassigned))
+}
+
+/// Recorre todos los bloques Unicode en orden.
+fn unicode_blocks() -> impl Iterator<Item = UnicodeBlock> {
+    let mut code = 0;
+    std::iter::from_fn(move || {
+        while code <= char::MAX as u32 {
+            match char::from_u32(code).and_then(find_unicode_block) {
+                Some(block) => {
+                    code = block.end() + 1;
+                    return Some(block);
                 }
+                None => code += 1,
             }
-            return Ok(chars);
+        }
+        None
+    })
+}
+
+/// Nombre normalizado para comparaciones flexibles (regla UAX44-LM3).
+fn loose_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '_'))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Caracteres con código entre `start` y `end`, ambos incluidos, sin los sustitutos.
+fn code_range(start: u32, end: u32) -> RangeSet {
+    if start > char::MAX as u32 {
+        return RangeSet::new();
+    }
+
+    let start = match start {
+        0xD800..=0xDFFF => '\u{E000}',
+        _ => char::from_u32(start).unwrap_or(char::MAX),
+    };
+    let end = match end {
+        0xD800..=0xDFFF => '\u{D7FF}',
+        _ => char::from_u32(end).unwrap_or(char::MAX),
+    };
+
+    RangeSet::from_range(start, end)
+}
+
+/// Verifica si la cadena es una clase entre corchetes. Una cadena que no termina en `]`
+/// (p. ej. `"[]{}()"`) se sigue tratando como literal.
+fn is_bracket_class(input: &str) -> bool {
+    input.len() > 2 && input.starts_with('[') && input.ends_with(']')
+}
+
+/// Analiza una clase entre corchetes como `[a-zA-Z0-9_]` o `[^"'\\]`.
+///
+/// Gramática:
+/// ```text
+/// clase    := '[' '^'? elemento+ ']'
+/// elemento := carácter ('-' carácter)?
+/// carácter := 'U+' hex{1,6} | '\' ('-' | ']' | '[' | '\' | '^' | 'U') | cualquier otro carácter
+/// ```
+/// Un `-` al principio o al final de la clase es literal. `[^...]` es el complemento sobre
+/// todos los puntos de código Unicode. Los errores indican la columna (empezando en 1).
+fn parse_bracket_class(input: &str) -> Result<RangeSet> {
+    let mut parser = ClassParser {
+        input,
+        chars: input.chars().collect(),
+        pos: 1,
+    };
+
+    parser.class()
+}
+
+struct ClassParser<'a> {
+    input: &'a str,
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl ClassParser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_next(&self) -> Option<char> {
+        self.chars.get(self.pos + 1).copied()
+    }
+
+    fn error(&self, pos: usize, reason: &str) -> UtilsError {
+        UtilsError::InvalidClass {
+            input: self.input.to_string(),
+            column: pos + 1,
+            reason: reason.to_string(),
+        }
+    }
+
+    /// `'^'? elemento+ ']'`, con el `[` inicial ya consumido.
+    fn class(&mut self) -> Result<RangeSet> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+
+        let mut ranges = Vec::new();
+        loop {
+            match self.peek() {
+                Some(']') => break,
+                Some(_) => {
+                    let start_pos = self.pos;
+                    let start = self.character()?;
+                    let end = if self.peek() == Some('-') && !matches!(self.peek_next(), Some(']') | None) {
+                        self.pos += 1;
+                        self.character()?
+                    } else {
+                        start
+                    };
+
+                    if start > end {
+                        return Err(self.error(start_pos, "rango invertido (inicio > fin)"));
+                    }
+                    ranges.push((start, end));
+                }
+                None => return Err(self.error(self.pos, "falta el ']' de cierre")),
+            }
+        }
+
+        if ranges.is_empty() {
+            return Err(self.error(self.pos, "la clase está vacía"));
+        }
+        self.pos += 1;
+        if self.pos < self.chars.len() {
+            return Err(self.error(self.pos, "hay texto después del ']' de cierre"));
+        }
+
+        let class = RangeSet::from_ranges(ranges);
+        Ok(if negated { class.complement() } else { class })
+    }
+
+    /// Un carácter de la clase: código `U+XXXX`, escape o carácter literal.
+    fn character(&mut self) -> Result<char> {
+        let start = self.pos;
+
+        match self.peek() {
+            Some('\\') => {
+                self.pos += 1;
+                match self.peek() {
+                    Some(c @ ('-' | ']' | '[' | '\\' | '^' | 'U')) => {
+                        self.pos += 1;
+                        Ok(c)
+                    }
+                    _ => Err(self.error(start, "escape inválido (se admiten \\-, \\], \\[, \\\\, \\^ y \\U)")),
+                }
+            }
+            Some('U') if self.peek_next() == Some('+') => {
+                self.pos += 2;
+                let digits = self.pos;
+                while self.pos - digits < 6 && self.peek().is_some_and(|c| c.is_ascii_hexdigit()) {
+                    self.pos += 1;
+                }
+
+                let hex: String = self.chars[digits..self.pos].iter().collect();
+                if hex.is_empty() {
+                    return Err(self.error(digits, "se esperaban dígitos hexadecimales después de 'U+'"));
+                }
+                u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| self.error(start, "código Unicode inválido"))
+            }
+            Some(c) => {
+                self.pos += 1;
+                Ok(c)
+            }
+            None => Err(self.error(start, "se esperaba un carácter")),
         }
     }
 }
@@ -75,7 +523,7 @@ fn is_unicode_hex_range(input: &str) -> bool {
 }
 
 /// Parsea rango Unicode "U+XXXX-U+YYYY"
-fn parse_unicode_hex_range(range: &str) -> Result<Vec<char>> {
+fn parse_unicode_hex_range(range: &str) -> Result<RangeSet> {
     let parts: Vec<&str> = range.split('-').collect();
     let start_code = parse_single_unicode_hex(parts[0])?;
     let end_code = parse_single_unicode_hex(parts[1])?;
@@ -84,13 +532,8 @@ fn parse_unicode_hex_range(range: &str) -> Result<Vec<char>> {
         return Err(UtilsError::InvalidRange(range.to_string()));
     }
 
-    let mut chars = Vec::new();
-    for code in start_code..=end_code {
-        if let Some(c) = char::from_u32(code) {
-            chars.push(c);
-        }
-    }
-    Ok(chars)
+    // Los códigos que no son `char` (sustitutos o mayores que U+10FFFF) se descartan.
+    Ok(code_range(start_code, end_code))
 }
 
 /// Verifica si es un rango de un solo grafema a un solo grafema, como "a-z", "0-9", "Ñ-ß", etc.
@@ -104,26 +547,17 @@ fn is_single_grapheme_range(input: &str) -> bool {
 }
 
 /// Parsea un rango de un solo grafema, p. ej. "a-z", "0-9"
-fn parse_single_char_range(range: &str) -> Result<Vec<char>> {
+fn parse_single_char_range(range: &str) -> Result<RangeSet> {
     let parts: Vec<&str> = range.split('-').collect();
     // Podemos tomar el primer char de cada grapheme
     let start_char = parts[0].chars().next().unwrap();
     let end_char = parts[1].chars().next().unwrap();
 
-    let start = start_char as u32;
-    let end = end_char as u32;
-
-    if start > end {
+    if start_char > end_char {
         return Err(UtilsError::InvalidRange(range.to_string()));
     }
 
-    let mut chars = Vec::new();
-    for code in start..=end {
-        if let Some(c) = char::from_u32(code) {
-            chars.push(c);
-        }
-    }
-    Ok(chars)
+    Ok(RangeSet::from_range(start_char, end_char))
 }
 
 /// Verifica si la cadena es un único codepoint en formato "U+XXXX"
@@ -141,3 +575,534 @@ fn parse_single_unicode_hex(input: &str) -> Result<u32> {
     let code_u32 = u32::from_str_radix(hex_part, 16).map_err(|_| UtilsError::InvalidFormat(input.to_string()))?;
     Ok(code_u32)
 }
+
+/// Operación entre conjuntos de caracteres dentro de una expresión como `"@ascii_letters - aeiou"`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SetOperation {
+    /// ` + `: caracteres de cualquiera de los dos conjuntos.
+    Union,
+    /// ` - `: caracteres del primer conjunto que no están en el segundo.
+    Difference,
+    /// ` & `: caracteres presentes en ambos conjuntos.
+    Intersection,
+}
+
+/// Evalúa `input` como expresión de conjuntos: operandos separados por ` + `, ` - ` o ` & `,
+/// siempre con espacios alrededor del operador, y evaluados de izquierda a derecha. `operand`
+/// expande cada operando (rango, literal, `U+XXXX` o `@nombre`). Un operando `\+`, `\-` o `\&`
+/// es el carácter literal.
+///
+/// Si `input` no tiene forma de expresión (p. ej. `"a-z"` o `"hola mundo"`), se expande entero
+/// con `operand`, como hasta ahora, tras quitar la barra a los operadores escapados: `"x \- y"`
+/// es el texto `"x - y"`.
+pub fn evaluate_set_expression<E, F>(input: &str, mut operand: F) -> std::result::Result<GraphemeSet, E>
+where
+    E: From<UtilsError>,
+    F: FnMut(&str) -> std::result::Result<GraphemeSet, E>,
+{
+    let Some((first, rest)) = parse_set_expression(input)? else {
+        return operand(&unescape_operators(input));
+    };
+
+    let mut set = operand(first)?;
+    for (operation, item) in rest {
+        let other = operand(item)?;
+        set = match operation {
+            SetOperation::Union => set.union(&other),
+            SetOperation::Difference => set.difference(&other),
+            SetOperation::Intersection => set.intersection(&other),
+        };
+    }
+
+    Ok(set)
+}
+
+/// Operadores escapados con una barra invertida, que se interpretan como el carácter literal.
+const ESCAPED_OPERATORS: [(&str, &str); 3] = [("\\+", "+"), ("\\-", "-"), ("\\&", "&")];
+
+/// Carácter de `token` si es un operador escapado (`\+`, `\-` o `\&`).
+fn escaped_operator(token: &str) -> Option<&'static str> {
+    ESCAPED_OPERATORS
+        .iter()
+        .find(|(escaped, _)| *escaped == token)
+        .map(|(_, operator)| *operator)
+}
+
+/// Quita la barra a los operadores escapados de `input`, respetando los espacios.
+fn unescape_operators(input: &str) -> Cow<'_, str> {
+    if !input.split_whitespace().any(|token| escaped_operator(token).is_some()) {
+        return Cow::Borrowed(input);
+    }
+
+    // Cada trozo es un token seguido, como mucho, de un carácter de espacio.
+    let mut output = String::with_capacity(input.len());
+    for piece in input.split_inclusive(char::is_whitespace) {
+        let token = piece.trim_end_matches(char::is_whitespace);
+        output.push_str(escaped_operator(token).unwrap_or(token));
+        output.push_str(&piece[token.len()..]);
+    }
+    Cow::Owned(output)
+}
+
+/// Divide `input` en tokens separados por espacios. Una clase entre corchetes (`[a - c]`) o un
+/// selector de propiedad (`\p{Script = Greek}`) es un único token aunque tenga espacios, y un
+/// bloque (`block:Latin 1 Supplement`) llega hasta el siguiente operador.
+///
+/// Si una clase o un selector no se cierran, la cadena solo se acepta cuando no tiene espacios:
+/// entonces es un único token y se interpreta como texto literal, como `"[abc"`.
+fn tokenize(input: &str) -> Result<Vec<&str>> {
+    let unclosed = |what: &str| UtilsError::InvalidExpression {
+        input: input.to_string(),
+        reason: format!("falta el cierre de {}", what),
+    };
+    let spaced = input.trim().contains(char::is_whitespace);
+
+    let mut tokens = Vec::new();
+    let mut rest = input.trim_start();
+    while !rest.is_empty() {
+        let opened = if rest.starts_with('[') {
+            Some((class_length(rest), "la clase '['"))
+        } else if rest.starts_with("\\p{") || rest.starts_with("\\P{") {
+            Some((rest.find('}').map(|end| end + 1), "la propiedad '{'"))
+        } else {
+            None
+        };
+
+        let mut end = match opened {
+            Some((Some(length), _)) => length,
+            Some((None, what)) if spaced => return Err(unclosed(what)),
+            _ if rest.starts_with("block:") => block_length(rest),
+            _ => 0,
+        };
+        end += rest[end..].find(char::is_whitespace).unwrap_or(rest.len() - end);
+
+        tokens.push(&rest[..end]);
+        rest = rest[end..].trim_start();
+    }
+
+    Ok(tokens)
+}
+
+/// Longitud en bytes de la clase entre corchetes con la que empieza `input`, hasta el primer
+/// `]` sin escapar, o `None` si no se cierra.
+fn class_length(input: &str) -> Option<usize> {
+    let mut chars = input.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            ']' => return Some(i + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Longitud en bytes del bloque `block:...` con el que empieza `input`: sus palabras llegan
+/// hasta el siguiente operador o el final.
+fn block_length(input: &str) -> usize {
+    let mut end = 0;
+    loop {
+        end += input[end..].find(char::is_whitespace).unwrap_or(input.len() - end);
+        let next = input[end..].trim_start();
+        let word = next.split_whitespace().next();
+        if matches!(word, None | Some("+" | "-" | "&")) {
+            return end;
+        }
+        end = input.len() - next.len();
+    }
+}
+
+/// Expresión de conjuntos dividida: primer operando y pares (operación, operando).
+type SetExpression<'a> = (&'a str, Vec<(SetOperation, &'a str)>);
+
+/// Divide una expresión de conjuntos en su primer operando y los pares (operación, operando)
+/// que le siguen. Devuelve `None` si `input` no es una expresión.
+///
+/// Si todos los operandos son caracteres sueltos (`"x - y"`, `"a + b"`), la cadena podría ser
+/// tanto una expresión como un texto literal, así que se rechaza pidiendo que se escriba de
+/// forma inequívoca.
+fn parse_set_expression(input: &str) -> Result<Option<SetExpression<'_>>> {
+    let tokens = tokenize(input)?;
+    let Some((first, rest)) = tokens.split_first() else {
+        return Ok(None);
+    };
+    if rest.is_empty() || rest.len() % 2 != 0 {
+        return Ok(None);
+    }
+
+    let mut operations = Vec::with_capacity(rest.len() / 2);
+    for pair in rest.chunks(2) {
+        let operation = match pair[0] {
+            "+" => SetOperation::Union,
+            "-" => SetOperation::Difference,
+            "&" => SetOperation::Intersection,
+            _ => return Ok(None),
+        };
+        operations.push((operation, pair[1]));
+    }
+
+    // Un operando con más `]` que `[` suele ser una clase partida por los espacios (`"[a - c]"`
+    // sin el tokenizador la dividiría en `"[a"` y `"c]"`).
+    let operands = || std::iter::once(*first).chain(operations.iter().map(|(_, item)| *item));
+    let unbalanced =
+        |operand: &&str| !operand.starts_with('[') && operand.matches(']').count() > operand.matches('[').count();
+    if let Some(operand) = operands().find(unbalanced) {
+        return Err(UtilsError::InvalidExpression {
+            input: input.to_string(),
+            reason: format!("el operando '{}' tiene un ']' sin su '[' de apertura", operand),
+        });
+    }
+
+    let single = |token: &str| is_literal(token) && token.graphemes(true).count() == 1;
+    if single(first) && operations.iter().all(|(_, item)| single(item)) {
+        return Err(UtilsError::InvalidExpression {
+            input: input.to_string(),
+            reason: "todos los operandos son caracteres sueltos. Para el texto literal, escribe los \
+                     operadores con una barra invertida delante (\"x \\- y\"); para un rango, sin \
+                     espacios (\"x-y\")"
+                .into(),
+        });
+    }
+
+    let first = escaped_operator(first).unwrap_or(first);
+    let operations = operations
+        .into_iter()
+        .map(|(operation, item)| (operation, escaped_operator(item).unwrap_or(item)))
+        .collect();
+    Ok(Some((first, operations)))
+}
+
+/// Categoría de caracteres que el filtro de caracteres imprimibles elimina por defecto: no se
+/// pueden escribir con un teclado o rompen otros sistemas al aparecer en una contraseña.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UnsafeKind {
+    /// No-caracteres (`U+FDD0-U+FDEF` y los dos últimos de cada plano).
+    Noncharacter,
+    /// Controles C0 y C1 (`\p{Cc}`).
+    Control,
+    /// Controles de dirección del texto (`U+202A-U+202E`, `U+2066-U+2069`, ...), que hacen que
+    /// una cadena se muestre en un orden distinto del real ("Trojan Source").
+    Bidi,
+    /// Caracteres de formato invisibles como ZWJ o el espacio de ancho cero (`\p{Cf}`), salvo
+    /// los de `Bidi`.
+    Format,
+    /// Otros caracteres que no se ven pero tampoco son de formato: selectores de variación,
+    /// el `U+034F` y los rellenos hangul (`\p{Default_Ignorable_Code_Point}`).
+    Invisible,
+    /// Uso privado (`\p{Co}`).
+    PrivateUse,
+    /// Puntos de código sin asignar (`\p{Cn}`).
+    Unassigned,
+}
+
+impl UnsafeKind {
+    /// Todas las categorías, en el orden en que se comprueban.
+    pub const ALL: [UnsafeKind; 7] = [
+        UnsafeKind::Noncharacter,
+        UnsafeKind::Control,
+        UnsafeKind::Bidi,
+        UnsafeKind::Format,
+        UnsafeKind::Invisible,
+        UnsafeKind::PrivateUse,
+        UnsafeKind::Unassigned,
+    ];
+
+    /// Nombre de la categoría tal como se escribe en `allow`.
+    pub fn name(self) -> &'static str {
+        match self {
+            UnsafeKind::Noncharacter => "noncharacter",
+            UnsafeKind::Control => "control",
+            UnsafeKind::Bidi => "bidi",
+            UnsafeKind::Format => "format",
+            UnsafeKind::Invisible => "invisible",
+            UnsafeKind::PrivateUse => "private-use",
+            UnsafeKind::Unassigned => "unassigned",
+        }
+    }
+
+    fn selector(self) -> &'static str {
+        match self {
+            UnsafeKind::Noncharacter => "\\p{Noncharacter_Code_Point}",
+            UnsafeKind::Control => "\\p{Cc}",
+            UnsafeKind::Bidi => "\\p{Bidi_Control}",
+            // Cada categoría es disjunta de las demás, para que `allow = ["format"]` no permita
+            // también los controles de dirección.
+            UnsafeKind::Format => "[\\p{Cf}--\\p{Bidi_Control}]",
+            UnsafeKind::Invisible => "[\\p{Default_Ignorable_Code_Point}--\\p{Cf}--\\p{Cn}]",
+            UnsafeKind::PrivateUse => "\\p{Co}",
+            UnsafeKind::Unassigned => "\\p{Cn}",
+        }
+    }
+}
+
+/// Indica si `chars[i]` une las partes de una secuencia de emoji bien formada, el único caso en
+/// que un carácter invisible se admite sin `allow`: un ZWJ entre dos pictogramas (`👨‍👩‍👧`), el
+/// selector de presentación emoji `U+FE0F` tras un emoji (`❤️`) o las etiquetas de una bandera
+/// de subdivisión (`🏴` seguida de etiquetas y terminada en `U+E007F`).
+fn joins_emoji(chars: &[char], i: usize) -> bool {
+    static PICTOGRAPHIC: OnceLock<RangeSet> = OnceLock::new();
+    static EMOJI: OnceLock<RangeSet> = OnceLock::new();
+    static MODIFIER: OnceLock<RangeSet> = OnceLock::new();
+
+    const ZWJ: char = '\u{200D}';
+    const VS16: char = '\u{FE0F}';
+    const BLACK_FLAG: char = '\u{1F3F4}';
+    const CANCEL_TAG: char = '\u{E007F}';
+
+    let pictographic = PICTOGRAPHIC.get_or_init(|| property_set("\\p{Extended_Pictographic}").unwrap_or_default());
+    let emoji = EMOJI.get_or_init(|| property_set("\\p{Emoji}").unwrap_or_default());
+    let modifier = MODIFIER.get_or_init(|| property_set("\\p{Emoji_Modifier}").unwrap_or_default());
+
+    match chars[i] {
+        ZWJ => {
+            // El pictograma anterior puede llevar su selector de presentación o un tono de piel.
+            let before = chars[..i].iter().rev().find(|&&c| c != VS16 && !modifier.contains(c));
+            matches!(before, Some(&c) if pictographic.contains(c))
+                && matches!(chars.get(i + 1), Some(&c) if pictographic.contains(c))
+        }
+        VS16 => i > 0 && emoji.contains(chars[i - 1]),
+        '\u{E0020}'..=CANCEL_TAG => {
+            chars.len() > 2
+                && chars[0] == BLACK_FLAG
+                && chars[chars.len() - 1] == CANCEL_TAG
+                && chars[1..chars.len() - 1]
+                    .iter()
+                    .all(|c| ('\u{E0020}'..'\u{E007F}').contains(c))
+        }
+        _ => false,
+    }
+}
+
+/// Caracteres de cada categoría insegura, en el orden de `UnsafeKind::ALL`.
+fn unsafe_sets() -> &'static [(UnsafeKind, RangeSet)] {
+    static SETS: OnceLock<Vec<(UnsafeKind, RangeSet)>> = OnceLock::new();
+
+    SETS.get_or_init(|| {
+        UnsafeKind::ALL
+            .iter()
+            .filter_map(|&kind| Some((kind, property_set(kind.selector())?)))
+            .collect()
+    })
+}
+
+/// Busca en `text` los caracteres de categorías inseguras, sin excepciones. Sirve para revisar
+/// cualquier cadena (una contraseña, un archivo de configuración) en busca de controles de
+/// dirección o caracteres invisibles. Devuelve la posición en bytes, el carácter y su categoría
+/// de cada uno, en orden.
+pub fn find_unsafe(text: &str) -> Vec<(usize, char, UnsafeKind)> {
+    let sets = unsafe_sets();
+
+    text.char_indices()
+        .filter_map(|(offset, c)| {
+            let (kind, _) = sets.iter().find(|(_, chars)| chars.contains(c))?;
+            Some((offset, c, *kind))
+        })
+        .collect()
+}
+
+/// Elimina de `set` los caracteres de categorías inseguras que no estén en `allow`.
+/// Devuelve los elementos eliminados agrupados por categoría.
+///
+/// Dentro de un grafema compuesto solo se admiten los caracteres que unen una secuencia de
+/// emoji bien formada (ver `joins_emoji`). Si contiene cualquier otro carácter de una categoría
+/// insegura, se elimina el grafema entero.
+pub fn filter_unsafe(set: &mut GraphemeSet, allow: &[UnsafeKind]) -> BTreeMap<UnsafeKind, GraphemeSet> {
+    let mut removed: BTreeMap<UnsafeKind, GraphemeSet> = BTreeMap::new();
+    for (kind, unsafe_chars) in unsafe_sets() {
+        if allow.contains(kind) {
+            continue;
+        }
+
+        let mut found = GraphemeSet::from(set.chars().intersection(unsafe_chars));
+        for cluster in set.clusters() {
+            let chars: Vec<char> = cluster.chars().collect();
+            if (0..chars.len()).any(|i| unsafe_chars.contains(chars[i]) && !joins_emoji(&chars, i)) {
+                found.insert(cluster);
+            }
+        }
+
+        if !found.is_empty() {
+            *set = set.difference(&found);
+            removed.insert(*kind, found);
+        }
+    }
+
+    removed
+}
+
+/// Elimina de `set` los elementos que no forman un grafema por sí solos: pegados a otro
+/// elemento se fundirían con él en un único grafema y la contraseña tendría menos grafemas de
+/// los pedidos. Son las marcas combinantes sueltas, el ZWJ, los caracteres que se unen al
+/// siguiente (`Prepend`), los indicadores regionales sueltos y los jamos hangul sueltos.
+/// Devuelve los elementos eliminados.
+pub fn remove_dependent(set: &mut GraphemeSet) -> GraphemeSet {
+    static DEPENDENT: OnceLock<RangeSet> = OnceLock::new();
+
+    let dependent = DEPENDENT.get_or_init(|| {
+        [
+            "Extend",
+            "SpacingMark",
+            "ZWJ",
+            "Prepend",
+            "Regional_Indicator",
+            "L",
+            "V",
+            "T",
+        ]
+        .iter()
+        .filter_map(|value| property_set(&format!("\\p{{Grapheme_Cluster_Break={}}}", value)))
+        .fold(RangeSet::new(), |all, chars| all.union(&chars))
+    });
+
+    let mut found = GraphemeSet::from(set.chars().intersection(dependent));
+    for cluster in set.clusters() {
+        if !is_standalone(cluster) {
+            found.insert(cluster);
+        }
+    }
+
+    *set = set.difference(&found);
+    found
+}
+
+/// Indica si el grafema compuesto `cluster` sigue siendo un grafema aparte junto a cualquier
+/// otro elemento válido. Se comprueba contra un representante de cada forma en que un grafema
+/// puede empezar o terminar y unirse con el vecino: letras, sílabas hangul, banderas,
+/// pictogramas y consonantes índicas (que se unen a través de un virama).
+fn is_standalone(cluster: &str) -> bool {
+    const NEIGHBOURS: [&str; 6] = ["a", "가", "각", "🇪🇸", "😀", "क"];
+
+    cluster.graphemes(true).count() == 1
+        && NEIGHBOURS.iter().all(|neighbour| {
+            [format!("{}{}", neighbour, cluster), format!("{}{}", cluster, neighbour)]
+                .iter()
+                .all(|pair| pair.graphemes(true).count() == 2)
+        })
+}
+
+/// Grupos de caracteres que se confunden al leerlos en voz alta, impresos o escritos a mano,
+/// aunque en Unicode no se consideren iguales. Completan los esqueletos de UTS #39, que solo
+/// cubren caracteres visualmente idénticos (`0` y `O`, `1`, `l` e `I`).
+const AMBIGUOUS: &[&str] = &["0Oo", "1lI|", "2Zz", "5Ss", "6G", "8B", "9gq", "uv", "'`"];
+
+/// Elimina de `set` los elementos que se pueden confundir con otro elemento del conjunto.
+/// Dos elementos se confunden si tienen el mismo esqueleto de UTS #39 (lo que incluye parecidos
+/// entre escrituras, como la `а` cirílica y la `a` latina) o si están en el mismo grupo de
+/// `AMBIGUOUS`. Se eliminan todos los elementos del grupo, porque quien lee la contraseña no
+/// puede saber cuál de ellos es. Devuelve los elementos eliminados.
+pub fn remove_ambiguous(set: &mut GraphemeSet) -> GraphemeSet {
+    let mut groups: HashMap<String, Vec<String>> = HashMap::new();
+    for element in set.iter() {
+        groups.entry(confusable_key(&element)).or_default().push(element);
+    }
+
+    let found: GraphemeSet = groups
+        .values()
+        .filter(|group| group.len() > 1)
+        .flatten()
+        .map(String::as_str)
+        .collect();
+
+    *set = set.difference(&found);
+    found
+}
+
+/// Clave que comparten los elementos que se confunden entre sí: su esqueleto de UTS #39, o el
+/// del primer carácter de su grupo en `AMBIGUOUS`.
+fn confusable_key(element: &str) -> String {
+    static GROUPS: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+    let groups = GROUPS.get_or_init(|| {
+        let mut groups = HashMap::new();
+        for group in AMBIGUOUS {
+            let skeletons: Vec<String> = group.chars().map(|c| skeleton(&c.to_string()).collect()).collect();
+            for member in &skeletons {
+                groups.insert(member.clone(), skeletons[0].clone());
+            }
+        }
+        groups
+    });
+
+    let key: String = skeleton(element).collect();
+    groups.get(&key).cloned().unwrap_or(key)
+}
+
+/// Forma normal de Unicode bajo la que la contraseña generada no debe cambiar. Si el sistema que
+/// la recibe normaliza la entrada, una contraseña que cambia al normalizarla deja de coincidir
+/// con la guardada.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NormalForm {
+    /// Composición canónica: `e` seguido de `U+0301` pasa a ser `é` (`U+00E9`).
+    #[default]
+    Nfc,
+    /// Composición de compatibilidad: además unifica variantes como `ﬁ` y `fi` o `²` y `2`.
+    Nfkc,
+}
+
+impl NormalForm {
+    pub fn name(self) -> &'static str {
+        match self {
+            NormalForm::Nfc => "NFC",
+            NormalForm::Nfkc => "NFKC",
+        }
+    }
+
+    /// Normaliza `text` con esta forma.
+    pub fn normalize(self, text: &str) -> String {
+        match self {
+            NormalForm::Nfc => text.nfc().collect(),
+            NormalForm::Nfkc => text.nfkc().collect(),
+        }
+    }
+
+    /// Indica si `element` puede formar parte de una contraseña estable: no cambia al
+    /// normalizarlo y tampoco se combina con el elemento anterior. Una concatenación de elementos
+    /// estables no cambia al normalizarla.
+    pub fn is_stable(self, element: &str) -> bool {
+        element.chars().next().is_none_or(|first| self.is_starter(first)) && self.normalize(element) == element
+    }
+
+    /// Indica si `c` no cambia al normalizarlo y no se combina ni se reordena con lo que tenga
+    /// delante: su clase combinante es 0 y la comprobación rápida de la forma normal responde
+    /// "Yes" (no "Maybe", que es el caso de las marcas y los jamos que se componen con el
+    /// carácter anterior).
+    fn is_starter(self, c: char) -> bool {
+        let quick = match self {
+            NormalForm::Nfc => is_nfc_quick(std::iter::once(c)),
+            NormalForm::Nfkc => is_nfkc_quick(std::iter::once(c)),
+        };
+        canonical_combining_class(c) == 0 && quick == IsNormalized::Yes
+    }
+
+    /// Caracteres que no son estables por sí solos.
+    fn unstable_chars(self) -> &'static RangeSet {
+        static NFC: OnceLock<RangeSet> = OnceLock::new();
+        static NFKC: OnceLock<RangeSet> = OnceLock::new();
+
+        let lock = match self {
+            NormalForm::Nfc => &NFC,
+            NormalForm::Nfkc => &NFKC,
+        };
+        lock.get_or_init(|| ('\0'..=char::MAX).filter(|&c| !self.is_starter(c)).collect())
+    }
+}
+
+/// Elimina de `set` los elementos que no son estables bajo `form` (ver `NormalForm::is_stable`):
+/// los que cambian al normalizarlos, como `Å` (`U+212B`) en NFC o `ﬁ` en NFKC, y los que se
+/// combinan con el elemento anterior, como una marca combinante suelta. Devuelve los elementos
+/// eliminados.
+pub fn remove_unnormalized(set: &mut GraphemeSet, form: NormalForm) -> GraphemeSet {
+    let mut found = GraphemeSet::from(set.chars().intersection(form.unstable_chars()));
+    for cluster in set.clusters() {
+        if !form.is_stable(cluster) {
+            found.insert(cluster);
+        }
+    }
+
+    *set = set.difference(&found);
+    found
+}
@@ -0,0 +1,89 @@
+mod common;
+
+use common::{alphabet, config, config_message};
+use kryptopass_utils::password_generator::{config::ConfigWarning, GenPassword};
+
+/// Alfabeto final de un único charset `value` con `avoid-ambiguous = true`.
+fn unambiguous(value: &str) -> String {
+    alphabet(&format!(
+        "[rules]\nlength = 12\navoid-ambiguous = true\n\n[charset]\nall = {}\n",
+        value
+    ))
+}
+
+#[test]
+fn whole_groups_of_look_alikes_are_removed() {
+    let alphabet = unambiguous("\"[a-zA-Z0-9]\"");
+    for c in "0Oo1lI2Zz5Ss6G8B9gquv".chars() {
+        assert!(!alphabet.contains(c), "{:?} in {:?}", c, alphabet);
+    }
+    for c in "347AaCcHhKkXx".chars() {
+        assert!(alphabet.contains(c), "{:?} not in {:?}", c, alphabet);
+    }
+}
+
+#[test]
+fn a_character_alone_in_its_group_is_kept() {
+    assert_eq!(unambiguous("\"@digits\""), "0123456789");
+    assert_eq!(unambiguous("\"0xyz\""), "0xyz");
+}
+
+#[test]
+fn confusables_across_scripts_are_removed() {
+    // `а` cirílica y `ο` griega tienen el mismo esqueleto que `a` y `o` latinas.
+    assert_eq!(unambiguous("\"a\u{430}o\u{3BF}xyz\""), "xyz");
+    assert_eq!(unambiguous("\"a\u{3BF}xyz\""), "axyz\u{3BF}");
+}
+
+#[test]
+fn include_reintroduces_ambiguous_characters_with_a_warning() {
+    let config = config(
+        r#"
+[rules]
+length = 12
+avoid-ambiguous = true
+include = ["0"]
+
+[charset]
+all = "0Oxyz"
+"#,
+    );
+    assert_eq!(config.get_charset().unwrap().iter().collect::<String>(), "0xyz");
+    assert_eq!(config.warnings(), [ConfigWarning::AmbiguousIncluded("0".into())]);
+}
+
+#[test]
+fn entropy_uses_the_reduced_alphabet() {
+    let body = |avoid: bool| {
+        format!(
+            "[rules]\nlength = 10\navoid-ambiguous = {}\n\n[charset]\nall = \"0Oxyz\"\n",
+            avoid
+        )
+    };
+    assert_eq!(config(&body(false)).entropy().unwrap().count, 5u32.pow(10).into());
+    assert_eq!(config(&body(true)).entropy().unwrap().count, 3u32.pow(10).into());
+}
+
+#[test]
+fn pattern_literals_cannot_be_ambiguous() {
+    let body = |literal: &str, include: &str| {
+        format!(
+            "[rules]\nlength = 12\navoid-ambiguous = true\ninclude = {}\npattern = '\"{}\"*'\n\n[charset]\nall = \"a-z\"\n",
+            include, literal
+        )
+    };
+
+    // `O` y `0` se confunden entre sí aunque ninguno esté en el alfabeto.
+    assert_eq!(
+        config_message(&body("O0", "[]")),
+        "Invalid configuration: The pattern literal \"O0\" contains 'O', which avoid-ambiguous removes \
+         because it can be confused with another character"
+    );
+    // `o` está en el alfabeto y `0` se confunde con ella.
+    assert!(config_message(&body("x0", "[]")).contains("contains '0'"));
+
+    // `include` los permite expresamente, igual que en el alfabeto.
+    let config = config(&body("O0", "['O0']"));
+    let password = GenPassword::seed_from_u64(config, 1).generate().unwrap().password;
+    assert!(password.starts_with("O0"), "{}", password);
+}
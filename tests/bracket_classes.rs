@@ -0,0 +1,85 @@
+mod common;
+
+use common::{alphabet, config_message};
+
+/// Configuración con un único charset `value`.
+fn single(value: &str) -> String {
+    format!("[rules]\nlength = 8\n\n[charset]\nchars = {}\n", value)
+}
+
+#[test]
+fn classes_describe_several_ranges() {
+    assert_eq!(alphabet(&single("'[a-cX-Z0-2_]'")), "012XYZ_abc");
+    assert_eq!(alphabet(&single("'[U+0391-U+0393]'")), "ΑΒΓ");
+}
+
+#[test]
+fn dashes_at_the_edges_and_escapes_are_literal() {
+    assert_eq!(alphabet(&single("'[-ab]'")), "-ab");
+    assert_eq!(alphabet(&single("'[ab-]'")), "-ab");
+    assert_eq!(alphabet(&single(r"'[\-\]\[\\\^\U+]'")), "+-U[\\]^");
+}
+
+#[test]
+fn negated_classes_combine_with_intersections() {
+    assert_eq!(alphabet(&single("'[^aeiou] & a-j'")), "bcdfghj");
+}
+
+#[test]
+fn classes_with_spaces_are_a_single_operand() {
+    // Dentro de la clase el espacio es un carácter más: `a`, el rango de ` ` a ` ` y `c`.
+    assert_eq!(alphabet(&single("'[a - c]'")), " ac");
+}
+
+#[test]
+fn classes_with_spaces_can_be_operands() {
+    assert_eq!(alphabet(&single("'[a-f ] - [ aeiou]'")), "bcdf");
+    assert_eq!(alphabet(&single("'@digits & [0-3 7]'")), "01237");
+}
+
+#[test]
+fn property_selectors_with_spaces_are_a_single_operand() {
+    assert_eq!(alphabet(&single(r"'\p{Script = Greek} & [α-γΑ-Γ] & \p{Lu}'")), "ΑΒΓ");
+}
+
+#[test]
+fn block_names_can_contain_spaces() {
+    assert_eq!(alphabet(&single("'block:Latin 1 Supplement & [à-â]'")), "àáâ");
+    assert_eq!(alphabet(&single("'[à-â] & block:Latin 1 Supplement'")), "àáâ");
+}
+
+#[test]
+fn unclosed_classes_are_rejected_in_expressions() {
+    assert_eq!(
+        config_message(&single("'[a-c - b'")),
+        "Invalid configuration: Expresión de conjuntos inválida '[a-c - b': falta el cierre de la clase '['"
+    );
+    assert_eq!(
+        config_message(&single("'a-c - b]'")),
+        "Invalid configuration: Expresión de conjuntos inválida 'a-c - b]': el operando 'b]' tiene un ']' sin su '[' de apertura"
+    );
+    assert_eq!(
+        config_message(&single(r"'\p{Lu & a-z'")),
+        "Invalid configuration: Expresión de conjuntos inválida '\\p{Lu & a-z': falta el cierre de la propiedad '{'"
+    );
+}
+
+#[test]
+fn strings_without_spaces_that_are_not_classes_stay_literal() {
+    assert_eq!(alphabet(&single("'[]{}()'")), "()[]{}");
+    assert_eq!(alphabet(&single("'[abc'")), "[abc");
+}
+
+#[test]
+fn malformed_classes_report_the_column() {
+    assert_eq!(
+        config_message(&single("'[z-a]'")),
+        "Invalid configuration: Error procesando '[z-a]' en charset 'chars': \
+         Clase inválida '[z-a]' en la columna 2: rango invertido (inicio > fin)"
+    );
+    assert_eq!(
+        config_message(&single("'[ab]c]'")),
+        "Invalid configuration: Error procesando '[ab]c]' en charset 'chars': \
+         Clase inválida '[ab]c]' en la columna 5: hay texto después del ']' de cierre"
+    );
+}
@@ -0,0 +1,38 @@
+//! Utilidades compartidas por las pruebas de integración.
+#![allow(dead_code)]
+
+use kryptopass_utils::password_generator::{config::Config, error::PasswordGenError};
+
+/// Cabecera mínima válida: `version` y `[profile]`.
+const HEADER: &str = r#"
+version = "0.1.0"
+
+[profile]
+id = "c9f00f9a-bdf0-4f19-8f61-5b3c012f4f5b"
+name = "tests"
+"#;
+
+/// Configuración formada por la cabecera y `body`. Falla si no es válida.
+pub fn config(body: &str) -> Config {
+    format!("{}{}", HEADER, body)
+        .parse()
+        .unwrap_or_else(|e| panic!("invalid configuration: {}", e))
+}
+
+/// Error al cargar la configuración formada por la cabecera y `body`. Falla si es válida.
+pub fn config_error(body: &str) -> PasswordGenError {
+    match format!("{}{}", HEADER, body).parse::<Config>() {
+        Ok(_) => panic!("the configuration was accepted"),
+        Err(e) => e,
+    }
+}
+
+/// Mensaje de `config_error`.
+pub fn config_message(body: &str) -> String {
+    config_error(body).to_string()
+}
+
+/// Alfabeto final de la configuración formada por la cabecera y `body`, en orden.
+pub fn alphabet(body: &str) -> String {
+    config(body).get_charset().unwrap().iter().collect()
+}
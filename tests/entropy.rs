@@ -0,0 +1,61 @@
+mod common;
+
+use common::{config, config_error, config_message};
+use kryptopass_utils::password_generator::{error::PasswordGenError, GenPassword};
+
+/// Configuración con `length = "auto"`, el charset `chars` y, si se indica, `min-entropy-bits`.
+fn auto(chars: &str, bits: Option<u8>) -> String {
+    let bits = bits
+        .map(|bits| format!("min-entropy-bits = {}\n", bits))
+        .unwrap_or_default();
+    format!(
+        "[rules]\nlength = \"auto\"\n{}\n[charset]\nchars = \"{}\"\n",
+        bits, chars
+    )
+}
+
+#[test]
+fn auto_length_reaches_the_default_entropy() {
+    // 26^17 da 79,9 bits, uno menos de los 80 por defecto: hace falta una letra más.
+    let config = config(&auto("a-z", None));
+    let entropy = config.entropy().unwrap();
+    assert_eq!(entropy.length, 18);
+    assert!(entropy.bits >= 80.0, "{}", entropy.bits);
+
+    let generated = GenPassword::seed_from_u64(config, 1).generate().unwrap();
+    assert_eq!(generated.length, 18);
+    assert_eq!(generated.password.chars().count(), 18);
+}
+
+#[test]
+fn min_entropy_bits_changes_the_auto_length() {
+    // 40 / log2(26) = 8,5 y 128 / log2(26) = 27,2.
+    assert_eq!(config(&auto("a-z", Some(40))).entropy().unwrap().length, 9);
+    assert_eq!(config(&auto("a-z", Some(128))).entropy().unwrap().length, 28);
+    // Con 16 símbolos cada carácter aporta exactamente 4 bits.
+    assert_eq!(config(&auto("@hex_lower", Some(40))).entropy().unwrap().length, 10);
+}
+
+#[test]
+fn auto_length_is_limited() {
+    // Con un único carácter ninguna longitud aporta entropía.
+    assert_eq!(
+        config_message(&auto("a", None)),
+        "Invalid configuration: No length up to 256 reaches 80 bits of entropy"
+    );
+    // Con dos caracteres, 255 bits necesitan 255 posiciones: cabe en el límite de 256.
+    assert_eq!(config(&auto("ab", Some(255))).entropy().unwrap().length, 255);
+}
+
+#[test]
+fn insufficient_entropy_is_rejected_at_load() {
+    // 26^8 son 37,6 bits, lejos de los 60 pedidos.
+    let error = config_error("[rules]\nlength = 8\nmin-entropy-bits = 60\n\n[charset]\nlower = \"a-z\"\n");
+    match error {
+        PasswordGenError::InsufficientEntropy { bits, required } => {
+            assert_eq!(required, 60);
+            assert!((bits - 8.0 * 26f64.log2()).abs() < 1e-9, "{}", bits);
+        }
+        other => panic!("{:?}", other),
+    }
+}
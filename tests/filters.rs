@@ -0,0 +1,182 @@
+mod common;
+
+use common::{alphabet, config, config_message};
+use kryptopass_utils::password_generator::{config::ConfigWarning, GenPassword};
+use kryptopass_utils::utils::{find_unsafe, UnsafeKind};
+
+/// Elementos del alfabeto final de una configuración por grafemas con el charset `value`.
+fn graphemes(value: &str) -> (Vec<String>, Vec<ConfigWarning>) {
+    let config = config(&format!(
+        "[rules]\nlength = 12\nunit = \"grapheme\"\n\n[charset]\nlowercase = \"x-z\"\nextra = {}\n",
+        value
+    ));
+    let elements = config.get_charset().unwrap().iter().collect();
+    (elements, config.warnings().to_vec())
+}
+
+#[test]
+fn well_formed_emoji_sequences_are_kept() {
+    let sequences = [
+        "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}",
+        "\u{1F44D}\u{1F3FD}\u{200D}\u{2640}\u{FE0F}",
+        "\u{2764}\u{FE0F}",
+        "\u{1F3F4}\u{E0067}\u{E0062}\u{E0073}\u{E0063}\u{E0074}\u{E007F}",
+    ];
+    let (elements, warnings) = graphemes(&format!("\"{}\"", sequences.concat()));
+    for sequence in sequences {
+        assert!(elements.contains(&sequence.to_string()), "{:?}", sequence);
+    }
+    assert!(warnings.is_empty(), "{:?}", warnings);
+}
+
+#[test]
+fn invisible_characters_outside_emoji_sequences_are_removed() {
+    let rejected = [
+        ("b\u{E0041}\u{E0042}", "format"),
+        ("a\u{FE00}", "invisible"),
+        ("b\u{200C}", "format"),
+        ("c\u{200D}", "format"),
+        ("\u{1F3F4}\u{E0067}\u{E0062}", "format"),
+        ("a\u{FE0F}", "invisible"),
+        ("\u{1F468}\u{200D}", "format"),
+    ];
+    for (element, category) in rejected {
+        let (elements, warnings) = graphemes(&format!("\"{}w\"", element));
+        assert_eq!(elements, ["w", "x", "y", "z"], "{:?}", element);
+        assert_eq!(
+            warnings,
+            [ConfigWarning::Removed {
+                source: "charset 'extra'".into(),
+                description: format!("{} character", category),
+                elements: vec![element.into()],
+                hint: Some(format!("add \"{}\" to allow in charset 'extra' to keep them", category)),
+            }],
+            "{:?}",
+            element
+        );
+    }
+}
+
+#[test]
+fn allow_keeps_invisible_characters_inside_clusters() {
+    let (elements, warnings) = graphemes("{ chars = [\"b\\u200C\", \"w\"], allow = [\"format\"] }");
+    assert!(elements.contains(&"b\u{200C}".to_string()));
+    assert!(warnings.is_empty(), "{:?}", warnings);
+}
+
+#[test]
+fn direction_controls_are_always_removed() {
+    let (elements, warnings) = graphemes("{ chars = [\"\\u202Ew\", \"a\"], allow = [\"format\", \"invisible\"] }");
+    assert_eq!(elements, ["a", "w", "x", "y", "z"]);
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].to_string().starts_with("Removed 1 bidi"), "{}", warnings[0]);
+}
+
+#[test]
+fn find_unsafe_reports_every_character() {
+    assert_eq!(
+        find_unsafe("a\u{202E}b\u{200B}\u{0007}"),
+        [
+            (1, '\u{202E}', UnsafeKind::Bidi),
+            (5, '\u{200B}', UnsafeKind::Format),
+            (8, '\u{0007}', UnsafeKind::Control),
+        ]
+    );
+}
+
+#[test]
+fn pattern_literals_with_unsafe_characters_are_rejected() {
+    let message = config_message(
+        r#"
+[rules]
+length = 8
+pattern = "\"a\u202Eb\"*"
+
+[charset]
+lowercase = "a-z"
+"#,
+    );
+    assert_eq!(
+        message,
+        "Invalid configuration: The pattern literal \"a\\u{202e}b\" contains the bidi character U+202E; \
+         add \"bidi\" to allow in a charset to use it"
+    );
+}
+
+#[test]
+fn pattern_literals_keep_categories_allowed_by_a_charset() {
+    let config = config(
+        r#"
+[rules]
+length = 8
+pattern = "\"a\u200Bb\"*"
+
+[charset]
+lowercase = "a-z"
+joiners = { chars = ["U+200B"], allow = ["format"] }
+"#,
+    );
+    let password = GenPassword::seed_from_u64(config, 1).generate().unwrap().password;
+    assert!(password.starts_with("a\u{200B}b"), "{:?}", password);
+}
+
+#[test]
+fn include_reports_the_category_that_removed_it() {
+    let message = config_message(
+        r#"
+[rules]
+length = 8
+include = ['U+202E']
+
+[charset]
+lowercase = "a-z"
+"#,
+    );
+    assert_eq!(
+        message,
+        "Invalid configuration: Every character in include was removed by the unsafe character filter (bidi: U+202E). \
+         Define them in a charset with allow = [\"bidi\"] and add it with @name to keep them"
+    );
+}
+
+/// Alfabeto final del charset `ñn@aá` con la restricción de teclado `keyboard`.
+fn typeable(keyboard: &str) -> String {
+    alphabet(&format!(
+        "[rules]\nlength = 8\nkeyboard = {}\n\n[charset]\nextra = \"ñn@aá\"\n",
+        keyboard
+    ))
+}
+
+#[test]
+fn keyboard_layouts_limit_the_alphabet() {
+    assert_eq!(typeable(r#"["es"]"#), "@anáñ");
+    assert_eq!(typeable(r#"["us"]"#), "@an");
+    assert_eq!(typeable(r#"["us", "es"]"#), "@an");
+    assert_eq!(typeable(r#"{ layouts = ["es"], altgr = false }"#), "anáñ");
+    assert_eq!(typeable(r#"{ layouts = ["es"], dead-keys = false }"#), "@anñ");
+}
+
+#[test]
+fn pattern_literals_must_be_typeable_on_every_layout() {
+    let body = |keyboard: &str, literal: &str| {
+        format!(
+            "[rules]\nlength = 8\nkeyboard = {}\npattern = '\"{}\"*'\n\n[charset]\nlowercase = \"a-z\"\n",
+            keyboard, literal
+        )
+    };
+
+    let password = GenPassword::seed_from_u64(config(&body(r#"["es"]"#, "año")), 1)
+        .generate()
+        .unwrap()
+        .password;
+    assert!(password.starts_with("año"), "{}", password);
+
+    assert_eq!(
+        config_message(&body(r#"["es", "us"]"#, "año")),
+        "Invalid configuration: The pattern literal \"año\" contains 'ñ', which cannot be typed on the 'us' keyboard layout"
+    );
+    assert_eq!(
+        config_message(&body(r#"{ layouts = ["es"], dead-keys = false }"#, "má")),
+        "Invalid configuration: The pattern literal \"má\" contains 'á', which cannot be typed on the 'es' keyboard layout"
+    );
+}
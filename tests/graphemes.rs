@@ -0,0 +1,55 @@
+mod common;
+
+use common::config;
+use kryptopass_utils::password_generator::GenPassword;
+use unicode_segmentation::UnicodeSegmentation;
+
+const FLAG: &str = "\u{1F1EA}\u{1F1F8}";
+const FAMILY: &str = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+
+#[test]
+fn length_counts_grapheme_clusters() {
+    let config = config(&format!(
+        "[rules]\nlength = 10\nunit = \"grapheme\"\n\n[charset]\nall = \"ab{}{}\"\n",
+        FLAG, FAMILY
+    ));
+    assert_eq!(config.get_charset().unwrap().len(), 4);
+
+    let mut generator = GenPassword::seed_from_u64(config, 2);
+    for _ in 0..50 {
+        let generated = generator.generate().unwrap();
+        let graphemes: Vec<&str> = generated.password.graphemes(true).collect();
+        assert_eq!(generated.length, 10);
+        assert_eq!(graphemes.len(), 10, "{:?}", generated.password);
+        assert!(
+            graphemes.iter().all(|g| ["a", "b", FLAG, FAMILY].contains(g)),
+            "{:?}",
+            graphemes
+        );
+    }
+}
+
+#[test]
+fn max_consecutive_counts_clusters_as_one_element() {
+    // Con dos elementos y sin repeticiones seguidas solo quedan las dos alternancias.
+    let alternating = config(&format!(
+        "[rules]\nlength = 6\nunit = \"grapheme\"\nmax-consecutive = 1\n\n[charset]\nall = \"{}{}\"\n",
+        FLAG, FAMILY
+    ));
+    assert_eq!(alternating.entropy().unwrap().count, 2u32.into());
+
+    let mut generator = GenPassword::seed_from_u64(alternating, 4);
+    for _ in 0..20 {
+        let password = generator.generate().unwrap().password;
+        let graphemes: Vec<&str> = password.graphemes(true).collect();
+        assert_eq!(graphemes.len(), 6);
+        assert!(graphemes.windows(2).all(|pair| pair[0] != pair[1]), "{:?}", graphemes);
+    }
+
+    // Una bandera repetida son dos elementos iguales seguidos, no cuatro indicadores regionales.
+    let repeated = config(&format!(
+        "[rules]\nlength = 3\nunit = \"grapheme\"\nmax-consecutive = 2\n\n[charset]\nall = \"x{}\"\n",
+        FLAG
+    ));
+    assert_eq!(repeated.entropy().unwrap().count, (8u32 - 2).into());
+}
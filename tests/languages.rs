@@ -0,0 +1,67 @@
+mod common;
+
+use common::{alphabet, config, config_message};
+use kryptopass_utils::password_generator::config::ConfigWarning;
+
+#[test]
+fn unknown_table_is_ignored_with_a_warning() {
+    let config = config(
+        r#"
+[rules]
+length = 12
+
+[charset]
+lowercase = "a-z"
+
+[meta]
+n = 3
+"#,
+    );
+    assert_eq!(config.warnings(), [ConfigWarning::UnknownSection("meta".into())]);
+    assert_eq!(
+        config.warnings()[0].to_string(),
+        "Section [meta] is ignored: it is neither a configuration section nor a language in profile.lang."
+    );
+}
+
+#[test]
+fn malformed_language_section_names_the_table() {
+    let message = config_message(
+        r#"lang = ["es"]
+
+[rules]
+length = 12
+
+[charset]
+lowercase = "a-z"
+
+[es]
+symbols = 3
+"#,
+    );
+    assert!(
+        message.starts_with("Invalid configuration: Invalid charset 'symbols' in section [es]"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn active_language_sections_extend_the_alphabet() {
+    let body = r#"lang = ["es"]
+
+[rules]
+length = 12
+
+[charset]
+lowercase = "a-z"
+
+[es]
+symbols = "¡¿"
+
+[de]
+symbols = "§"
+"#;
+    assert_eq!(alphabet(body), "abcdefghijklmnopqrstuvwxyz¡¿");
+    assert_eq!(config(body).warnings(), [ConfigWarning::IgnoredLanguage("de".into())]);
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use common::{alphabet, config, config_message};
+use kryptopass_utils::password_generator::config::ConfigWarning;
+
+#[test]
+fn literals_are_normalized_before_splitting() {
+    assert_eq!(
+        alphabet("[rules]\nlength = 12\n\n[charset]\nm = \"e\\u0301\\u212Bxyz\"\n"),
+        "xyz\u{C5}\u{E9}"
+    );
+    assert_eq!(
+        alphabet("[rules]\nlength = 12\nnormalization = \"nfkc\"\n\n[charset]\nm = \"\u{FB01}\u{B2}xyz\"\n"),
+        "2fixyz"
+    );
+}
+
+#[test]
+fn elements_that_change_are_removed_with_a_warning() {
+    let config = config(
+        r#"
+[rules]
+length = 12
+
+[charset]
+m = { chars = ["U+212B", "U+0300-U+0302", "xyz"] }
+"#,
+    );
+    assert_eq!(config.get_charset().unwrap().iter().collect::<String>(), "xyz");
+    assert_eq!(
+        config.warnings()[0].to_string(),
+        "Removed 4 characters that would change under NFC normalization from charset 'm': \
+         U+0300, U+0301, U+0302, U+212B."
+    );
+}
+
+#[test]
+fn nfc_keeps_compatibility_characters() {
+    let body = "[charset]\nm = { chars = [\"U+FB01\", \"U+00B2\", \"xyz\"] }\n";
+    let nfc = config(&format!("[rules]\nlength = 12\n\n{}", body));
+    assert_eq!(
+        nfc.get_charset().unwrap().iter().collect::<String>(),
+        "xyz\u{B2}\u{FB01}"
+    );
+    assert!(nfc.warnings().is_empty());
+
+    let nfkc = config(&format!("[rules]\nlength = 12\nnormalization = \"nfkc\"\n\n{}", body));
+    assert_eq!(nfkc.get_charset().unwrap().iter().collect::<String>(), "xyz");
+    assert!(matches!(&nfkc.warnings()[0], ConfigWarning::Removed { elements, .. } if elements.len() == 2));
+}
+
+#[test]
+fn graphemes_are_normalized_as_a_whole() {
+    assert_eq!(
+        alphabet("[rules]\nlength = 12\nunit = \"grapheme\"\n\n[charset]\nm = \"e\\u0301x\\u0323\\u0307yz\"\n"),
+        "yz\u{E9}\u{1E8B}\u{323}"
+    );
+}
+
+#[test]
+fn pattern_literals_that_change_are_an_error() {
+    assert_eq!(
+        config_message("[rules]\nlength = 12\npattern = '\"\u{212B}\"(m)*'\n\n[charset]\nm = \"xyz\"\n"),
+        "Invalid configuration: The pattern literal \"\u{212B}\" would change under NFC normalization"
+    );
+    assert_eq!(
+        config_message(
+            "[rules]\nlength = 12\nnormalization = \"nfkc\"\npattern = '\"\u{FB01}\"(m)*'\n\n[charset]\nm = \"xyz\"\n"
+        ),
+        "Invalid configuration: The pattern literal \"\u{FB01}\" would change under NFKC normalization"
+    );
+}
@@ -0,0 +1,285 @@
+mod common;
+
+use std::collections::BTreeSet;
+
+use common::{config, config_error, config_message};
+use kryptopass_utils::password_generator::{
+    error::PasswordGenError,
+    pattern::{parse_pattern, Node, Pattern, Quantifier},
+    GenPassword,
+};
+
+fn block(name: &str, negated: bool, min: usize, max: Option<usize>) -> Node {
+    Node::Block {
+        name: name.into(),
+        negated,
+        quantifier: Quantifier { min, max },
+    }
+}
+
+/// Columna, elemento esperado y elemento encontrado del error de `input`.
+fn error(input: &str) -> (usize, String, String) {
+    match parse_pattern(input) {
+        Err(PasswordGenError::InvalidPattern {
+            column,
+            expected,
+            found,
+        }) => (column, expected, found),
+        other => panic!("{:?} -> {:?}", input, other),
+    }
+}
+
+#[test]
+fn blocks_quantifiers_and_wildcards_are_parsed() {
+    let pattern = parse_pattern("(uppercase){1}(lowercase){3}(!digits){2,}(^symbols){0,4}*").unwrap();
+    assert_eq!(
+        pattern,
+        Pattern {
+            alternatives: vec![vec![
+                block("uppercase", false, 1, Some(1)),
+                block("lowercase", false, 3, Some(3)),
+                block("digits", true, 2, None),
+                block("symbols", true, 0, Some(4)),
+                Node::Wildcard,
+            ]],
+        }
+    );
+}
+
+#[test]
+fn groups_alternatives_and_literals_are_parsed() {
+    let pattern = parse_pattern(r#"((u)(l){2}|"\"\\"){3}|*"#).unwrap();
+    assert_eq!(
+        pattern,
+        Pattern {
+            alternatives: vec![
+                vec![Node::Group {
+                    alternatives: vec![
+                        vec![block("u", false, 1, Some(1)), block("l", false, 2, Some(2))],
+                        vec![Node::Literal(r#""\"#.into())],
+                    ],
+                    quantifier: Quantifier { min: 3, max: Some(3) },
+                }],
+                vec![Node::Wildcard],
+            ],
+        }
+    );
+}
+
+#[test]
+fn errors_report_the_column_and_what_was_expected() {
+    let cases: &[(&str, usize, &str, &str)] = &[
+        ("", 1, "'(', '\"' or '*'", "end of pattern"),
+        ("x", 1, "'(', '\"' or '*'", "'x'"),
+        ("|(a)", 1, "'(', '\"' or '*'", "'|'"),
+        ("(a)|", 5, "'(', '\"' or '*'", "end of pattern"),
+        ("()", 2, "charset name", "')'"),
+        ("(!)", 3, "charset name", "')'"),
+        ("(a", 3, "')'", "end of pattern"),
+        ("((a)", 5, "')'", "end of pattern"),
+        ("(a){", 5, "a number", "end of pattern"),
+        ("(a){,3}", 5, "a number", "','"),
+        ("(a){2", 6, "',' or '}'", "end of pattern"),
+        ("(a){2,x}", 7, "a number or '}'", "'x'"),
+        ("(a){5,2}", 7, "a maximum greater than or equal to 5", "'2'"),
+        ("(a){99999999999999999999999}", 5, "a smaller number", "'9'"),
+        ("(a)(b)c", 7, "'(', '\"', '*', '|' or end of pattern", "'c'"),
+        ("\"abc", 5, "closing '\"'", "end of pattern"),
+        ("\"a\\b\"", 4, "'\"' or '\\' after '\\'", "'b'"),
+    ];
+
+    for &(input, column, expected, found) in cases {
+        assert_eq!(
+            error(input),
+            (column, expected.to_string(), found.to_string()),
+            "pattern {:?}",
+            input
+        );
+    }
+}
+
+#[test]
+fn columns_count_characters_not_bytes() {
+    assert_eq!(error("\"ñé\"(a){x}").0, 9);
+}
+
+#[test]
+fn errors_are_reported_when_loading_the_configuration() {
+    let message = config_message(
+        r#"
+[rules]
+length = 8
+pattern = "(a){2"
+
+[charset]
+a = "a-z"
+"#,
+    );
+    assert_eq!(
+        message,
+        "Invalid pattern at column 6: expected ',' or '}', found end of pattern."
+    );
+}
+
+#[test]
+fn nested_repetitions_are_rejected_instead_of_exhausting_memory() {
+    let message = config_message(
+        r#"
+[rules]
+length = 30
+pattern = "(((d){0,30}){0,30}){0,30}"
+
+[charset]
+d = "0-9"
+"#,
+    );
+    assert_eq!(
+        message,
+        "Invalid configuration: The pattern is too complex: it needs more than 10000 automaton states. \
+         Use fewer nested repetitions or smaller counts"
+    );
+}
+
+#[test]
+fn long_counted_repetitions_are_accepted() {
+    let config = config(
+        r#"
+[rules]
+length = 2000
+pattern = "(d){2000}"
+
+[charset]
+d = "0-9"
+"#,
+    );
+    let password = GenPassword::seed_from_u64(config, 1).generate().unwrap().password;
+    assert_eq!(password.len(), 2000);
+    assert!(password.chars().all(|c| c.is_ascii_digit()));
+}
+
+/// Configuración con minúsculas, mayúsculas y dígitos, `length` y el patrón `pattern`.
+fn with_pattern(length: &str, pattern: &str) -> String {
+    format!(
+        "[rules]\nlength = {}\npattern = '{}'\n\n[charset]\nlower = \"a-z\"\nupper = \"A-Z\"\ndigits = \"0-9\"\n",
+        length, pattern
+    )
+}
+
+#[test]
+fn quantified_blocks_are_generated_in_order() {
+    let config = config(&with_pattern("8", "(upper){1}(lower){3}(digits){2,4}"));
+    let mut generator = GenPassword::seed_from_u64(config, 3);
+    for _ in 0..100 {
+        let password: Vec<char> = generator.generate().unwrap().password.chars().collect();
+        assert_eq!(password.len(), 8);
+        assert!(password[0].is_ascii_uppercase(), "{:?}", password);
+        assert!(password[1..4].iter().all(char::is_ascii_lowercase), "{:?}", password);
+        assert!(password[4..].iter().all(char::is_ascii_digit), "{:?}", password);
+    }
+}
+
+#[test]
+fn blocks_name_charsets_instead_of_ranges() {
+    // Un bloque solo admite el nombre de un charset o preset: el rango se define en [charset].
+    match config_error(&with_pattern("4", "(A-Z){4}")) {
+        PasswordGenError::CharsetNotFound(name) => assert_eq!(name, "A-Z"),
+        other => panic!("unexpected error: {}", other),
+    }
+
+    let config = config(&with_pattern("4", "(upper){4}"));
+    let password = GenPassword::seed_from_u64(config, 4).generate().unwrap().password;
+    assert!(password.chars().all(|c| c.is_ascii_uppercase()), "{}", password);
+}
+
+#[test]
+fn negated_blocks_use_the_rest_of_the_alphabet() {
+    let config = config(&with_pattern("6", "(!digits){3}(digits){3}"));
+    let mut generator = GenPassword::seed_from_u64(config, 8);
+    let mut seen_upper = false;
+    for _ in 0..100 {
+        let password: Vec<char> = generator.generate().unwrap().password.chars().collect();
+        assert!(password[..3].iter().all(char::is_ascii_alphabetic), "{:?}", password);
+        assert!(password[3..].iter().all(char::is_ascii_digit), "{:?}", password);
+        seen_upper |= password[..3].iter().any(char::is_ascii_uppercase);
+    }
+    assert!(seen_upper);
+}
+
+#[test]
+fn the_wildcard_pads_up_to_the_chosen_length() {
+    let config = config(&with_pattern("{ min = 6, max = 10 }", "(digits){2}*"));
+    let mut generator = GenPassword::seed_from_u64(config, 6);
+    let mut lengths = BTreeSet::new();
+    for _ in 0..200 {
+        let generated = generator.generate().unwrap();
+        let password: Vec<char> = generated.password.chars().collect();
+        assert_eq!(password.len(), generated.length);
+        assert!(password[..2].iter().all(char::is_ascii_digit), "{:?}", password);
+        assert!(password.iter().all(char::is_ascii_alphanumeric), "{:?}", password);
+        lengths.insert(generated.length);
+    }
+    // Cada longitud se elige en proporción a sus contraseñas: con 62 caracteres por posición
+    // libre, casi todas tienen la longitud máxima.
+    assert!(lengths.iter().all(|length| (6..=10).contains(length)), "{:?}", lengths);
+    assert!(lengths.contains(&10), "{:?}", lengths);
+}
+
+#[test]
+fn ranged_lengths_are_weighted_by_their_passwords() {
+    // Una contraseña de longitud 1 ("!") y 100 de longitud 2: "!" debe salir una de cada 101
+    // veces, no la mitad.
+    let config = config(&with_pattern("{ min = 1, max = 2 }", r#"(digits){2}|"!""#));
+    let mut generator = GenPassword::seed_from_u64(config, 12);
+    let mut short = 0;
+    for _ in 0..10_100 {
+        let generated = generator.generate().unwrap();
+        if generated.length == 1 {
+            assert_eq!(generated.password, "!");
+            short += 1;
+        }
+    }
+    assert!((70..=130).contains(&short), "\"!\" appeared {} times", short);
+}
+
+#[test]
+fn fixed_patterns_must_fit_the_length() {
+    assert_eq!(
+        config_message(&with_pattern("4", "(digits){5}")),
+        "Invalid configuration: The pattern requires at least 5 characters but the maximum length is 4"
+    );
+    assert_eq!(
+        config_message(&with_pattern("8", "(digits){5}")),
+        "Invalid configuration: The pattern allows at most 5 characters but the minimum length is 8"
+    );
+}
+
+#[test]
+fn literals_are_copied_as_prefix_and_suffix() {
+    let config = config(&with_pattern("{ min = 8, max = 12 }", r#""KP-"(digits){4}*"!""#));
+    let mut generator = GenPassword::seed_from_u64(config, 9);
+    for _ in 0..100 {
+        let generated = generator.generate().unwrap();
+        let password = generated.password;
+        assert_eq!(password.chars().count(), generated.length);
+        assert!(password.starts_with("KP-"), "{}", password);
+        assert!(password.ends_with('!'), "{}", password);
+        assert!(password[3..7].chars().all(|c| c.is_ascii_digit()), "{}", password);
+    }
+}
+
+#[test]
+fn several_wildcards_share_the_remaining_length() {
+    // Quedan 7 posiciones libres entre los dos comodines: el separador puede caer en cualquiera
+    // de las 8 posiciones posibles.
+    let config = config(&with_pattern("10", r#""<"*"|"*">""#));
+    let mut generator = GenPassword::seed_from_u64(config, 10);
+    let mut separators = BTreeSet::new();
+    for _ in 0..500 {
+        let password = generator.generate().unwrap().password;
+        assert_eq!(password.len(), 10);
+        assert!(password.starts_with('<') && password.ends_with('>'), "{}", password);
+        assert_eq!(password.matches('|').count(), 1, "{}", password);
+        separators.insert(password.find('|').unwrap());
+    }
+    assert_eq!(separators.into_iter().collect::<Vec<_>>(), (1..=8).collect::<Vec<_>>());
+}
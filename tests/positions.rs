@@ -0,0 +1,98 @@
+mod common;
+
+use common::{config, config_message};
+use kryptopass_utils::password_generator::GenPassword;
+
+#[test]
+fn positions_restrict_the_generated_characters() {
+    let config = config(
+        r#"
+[rules]
+length = { min = 4, max = 8 }
+first = "A-Z"
+last = "0-9"
+positions = { 1 = "-", -2 = "a" }
+
+[charset]
+lowercase = "a-z"
+uppercase = "A-Z"
+digits = "0-9"
+symbols = "-"
+"#,
+    );
+    let mut generator = GenPassword::seed_from_u64(config, 3);
+    for _ in 0..200 {
+        let password: Vec<char> = generator.generate().unwrap().password.chars().collect();
+        let n = password.len();
+        assert!((4..=8).contains(&n), "{:?}", password);
+        assert!(password[0].is_ascii_uppercase(), "{:?}", password);
+        assert_eq!(password[1], '-');
+        assert_eq!(password[n - 2], 'a');
+        assert!(password[n - 1].is_ascii_digit(), "{:?}", password);
+    }
+}
+
+#[test]
+fn language_alphabets_default_to_english_without_lang() {
+    // Sin `profile.lang`, `@letters` es el alfabeto inglés.
+    let config = config(
+        r#"
+[rules]
+length = 8
+positions = { 0 = "@letters" }
+
+[charset]
+alpha = "@ascii_letters"
+digits = "0-9"
+symbols = "@punctuation"
+"#,
+    );
+    let mut generator = GenPassword::seed_from_u64(config, 6);
+    for _ in 0..100 {
+        let password: Vec<char> = generator.generate().unwrap().password.chars().collect();
+        assert!(password[0].is_ascii_alphabetic(), "{:?}", password);
+    }
+}
+
+#[test]
+fn position_without_allowed_characters_is_an_error() {
+    assert_eq!(
+        config_message("[rules]\nlength = { min = 2, max = 6 }\npositions = { 4 = \"0\" }\n\n[charset]\nl = \"a-z\"\n"),
+        "Invalid configuration: No character of the alphabet or the pattern is allowed at position 4"
+    );
+}
+
+#[test]
+fn pattern_literals_count_as_allowed_characters() {
+    config("[rules]\nlength = 4\npattern = '\"#\"(l)*'\nfirst = \"#\"\n\n[charset]\nl = \"a-z\"\n");
+}
+
+#[test]
+fn lengths_made_impossible_by_positions_are_an_error() {
+    // Con longitud 1 la primera y la última posición coinciden y ningún carácter cumple ambas.
+    assert_eq!(
+        config_message(
+            "[rules]\nlength = { min = 1, max = 4 }\nfirst = \"a\"\nlast = \"b\"\n\n[charset]\nl = \"a-z\"\n"
+        ),
+        "Invalid configuration: No password of length 1 satisfies the position rules (first, last, positions)"
+    );
+    config("[rules]\nlength = { min = 2, max = 4 }\nfirst = \"a\"\nlast = \"b\"\n\n[charset]\nl = \"a-z\"\n");
+}
+
+#[test]
+fn lengths_impossible_for_other_reasons_are_still_skipped() {
+    let config =
+        config("[rules]\nlength = { min = 2, max = 6 }\npattern = '(l){4}'\nfirst = \"a\"\n\n[charset]\nl = \"a-z\"\n");
+    assert_eq!(config.entropy().unwrap().length, 4);
+}
+
+#[test]
+fn intersecting_constraints_on_one_position() {
+    assert_eq!(
+        config_message("[rules]\nlength = 4\nfirst = \"a-c\"\npositions = { 0 = \"x-z\" }\n\n[charset]\nl = \"a-z\"\n"),
+        "Invalid configuration: No character of the alphabet or the pattern is allowed at position 0"
+    );
+    let config =
+        config("[rules]\nlength = 4\nfirst = \"a-c\"\npositions = { 0 = \"b-z\" }\n\n[charset]\nl = \"a-z\"\n");
+    assert_eq!(config.entropy().unwrap().count, (2u32 * 26 * 26 * 26).into());
+}
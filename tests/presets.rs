@@ -0,0 +1,116 @@
+mod common;
+
+use common::{alphabet, config, config_message};
+use kryptopass_utils::password_generator::GenPassword;
+
+/// Configuración con un único charset `value`.
+fn single(value: &str) -> String {
+    format!("[rules]\nlength = 8\n\n[charset]\nchars = {}\n", value)
+}
+
+#[test]
+fn presets_expand_to_their_characters() {
+    assert_eq!(alphabet(&single("'@digits'")), "0123456789");
+    assert_eq!(alphabet(&single("'@hex_lower'")), "0123456789abcdef");
+    assert_eq!(alphabet(&single("'@octdigits'")), "01234567");
+    assert_eq!(alphabet(&single("'@base32'")), "234567ABCDEFGHIJKLMNOPQRSTUVWXYZ");
+    assert_eq!(alphabet(&single("'@printable'")).len(), 94);
+}
+
+#[test]
+fn base58_leaves_out_look_alikes() {
+    let base58 = alphabet(&single("'@base58'"));
+    assert_eq!(base58.len(), 58);
+    assert!(!base58.contains(['0', 'O', 'I', 'l']));
+}
+
+#[test]
+fn names_without_at_are_literals() {
+    assert_eq!(alphabet(&single("'digits'")), "dgist");
+}
+
+#[test]
+fn at_signs_that_are_not_references_are_literals() {
+    assert_eq!(alphabet(&single("'@'")), "@");
+    assert_eq!(alphabet(&single("'@#!'")), "!#@");
+}
+
+#[test]
+fn escaped_references_are_literals() {
+    assert_eq!(alphabet(&single(r"'\@abc'")), "@abc");
+    assert_eq!(alphabet(&single(r#""\\@digits""#)), "@dgist");
+}
+
+#[test]
+fn escaped_references_work_in_include_and_set_expressions() {
+    let body = r#"
+[rules]
+length = 8
+include = ['\@xy']
+
+[charset]
+chars = '@digits + \@ab'
+"#;
+    assert_eq!(alphabet(body), "0123456789@abxy");
+}
+
+#[test]
+fn unknown_presets_are_rejected() {
+    assert_eq!(
+        config_message(&single("'@digitos'")),
+        "Invalid configuration: Charset 'chars' references '@digitos', which is neither a charset nor a preset"
+    );
+}
+
+#[test]
+fn presets_can_be_used_in_include_exclude_and_patterns() {
+    let config = config(
+        r#"
+[rules]
+length = 8
+include = ["@octdigits"]
+exclude = ["@hex_lower"]
+pattern = "(@octdigits){4}*"
+
+[charset]
+lower = "a-h"
+"#,
+    );
+    assert_eq!(config.get_charset().unwrap().iter().collect::<String>(), "01234567gh");
+
+    let password = GenPassword::seed_from_u64(config, 5).generate().unwrap().password;
+    assert!(password[..4].chars().all(|c| ('0'..='7').contains(&c)), "{}", password);
+    assert!(password.chars().all(|c| "01234567gh".contains(c)), "{}", password);
+}
+
+#[test]
+fn cyclic_references_name_the_cycle() {
+    let message = config_message(
+        r#"
+[rules]
+length = 8
+
+[charset]
+a = "@b"
+b = "@a"
+"#,
+    );
+    assert_eq!(
+        message,
+        "Invalid configuration: Cyclic reference between charsets: a -> b -> a"
+    );
+
+    let message = config_message(
+        r#"
+[rules]
+length = 8
+
+[charset]
+a = "@a"
+"#,
+    );
+    assert_eq!(
+        message,
+        "Invalid configuration: Cyclic reference between charsets: a -> a"
+    );
+}
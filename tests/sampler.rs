@@ -0,0 +1,387 @@
+mod common;
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use common::{config, config_message};
+use kryptopass_utils::password_generator::GenPassword;
+
+#[test]
+fn long_passwords_do_not_exhaust_the_stack() {
+    // Cada posición es un nivel del recuento: con 4096 posiciones y un estado por posición, una
+    // implementación recursiva desborda la pila de los hilos de las pruebas.
+    let config = config(
+        r#"
+[rules]
+length = 4096
+max-consecutive = 2
+digits = { min = 1 }
+
+[charset]
+lowercase = "a-z"
+digits = "0-9"
+"#,
+    );
+    let password = GenPassword::seed_from_u64(config, 7).generate().unwrap().password;
+    assert_eq!(password.chars().count(), 4096);
+    assert!(password.chars().any(|c| c.is_ascii_digit()));
+}
+
+#[test]
+fn lengths_above_the_limit_are_rejected() {
+    let message = config_message(
+        r#"
+[rules]
+length = 50000
+
+[charset]
+lowercase = "a-z"
+"#,
+    );
+    assert_eq!(message, "Invalid configuration: Length cannot be greater than 4096");
+}
+
+#[test]
+fn count_matches_brute_force() {
+    let config = config(
+        r#"
+[rules]
+length = 5
+max-consecutive = 2
+digits = { min = 1, max = 2 }
+
+[charset]
+letters = "ab"
+digits = "12"
+"#,
+    );
+
+    let alphabet = ['a', 'b', '1', '2'];
+    let mut expected = 0u32;
+    for n in 0..4u32.pow(5) {
+        let password: Vec<char> = (0..5).map(|i| alphabet[(n / 4u32.pow(i) % 4) as usize]).collect();
+        let digits = password.iter().filter(|c| c.is_ascii_digit()).count();
+        let repeated = password.windows(3).any(|w| w[0] == w[1] && w[1] == w[2]);
+        if (1..=2).contains(&digits) && !repeated {
+            expected += 1;
+        }
+    }
+
+    assert_eq!(config.entropy().unwrap().count, expected.into());
+}
+
+#[test]
+fn unsatisfiable_minimums_are_rejected_at_load() {
+    let message = config_message(
+        r#"
+[rules]
+length = 12
+exclude = ["0-9"]
+digits = { min = 1 }
+
+[charset]
+lowercase = "a-z"
+digits = "0-9"
+"#,
+    );
+    assert_eq!(
+        message,
+        "Invalid configuration: No password satisfies the pattern, charset and position rules for the allowed lengths"
+    );
+}
+
+#[test]
+fn max_consecutive_count_matches_brute_force() {
+    for max in 1..=3 {
+        let config = config(&format!(
+            r#"
+[rules]
+length = 6
+max-consecutive = {}
+
+[charset]
+letters = "abc"
+"#,
+            max
+        ));
+
+        let mut expected = 0u32;
+        for n in 0..3u32.pow(6) {
+            let password: Vec<u32> = (0..6).map(|i| n / 3u32.pow(i) % 3).collect();
+            if !password.windows(max + 1).any(|w| w.iter().all(|&c| c == w[0])) {
+                expected += 1;
+            }
+        }
+
+        assert_eq!(
+            config.entropy().unwrap().count,
+            expected.into(),
+            "max-consecutive = {}",
+            max
+        );
+    }
+}
+
+#[test]
+fn max_consecutive_samples_uniformly() {
+    // Con "abc", longitud 3 y sin repeticiones seguidas hay 3 · 2 · 2 = 12 contraseñas.
+    let config = config(
+        r#"
+[rules]
+length = 3
+max-consecutive = 1
+
+[charset]
+letters = "abc"
+"#,
+    );
+    let mut generator = GenPassword::seed_from_u64(config, 9);
+
+    let mut frequencies: HashMap<String, u32> = HashMap::new();
+    for _ in 0..12_000 {
+        *frequencies.entry(generator.generate().unwrap().password).or_default() += 1;
+    }
+
+    assert_eq!(frequencies.len(), 12);
+    for (password, frequency) in frequencies {
+        let bytes = password.as_bytes();
+        assert!(bytes[0] != bytes[1] && bytes[1] != bytes[2], "{}", password);
+        assert!(
+            (850..=1150).contains(&frequency),
+            "{} appeared {} times",
+            password,
+            frequency
+        );
+    }
+}
+
+#[test]
+fn charset_counts_sample_uniformly() {
+    // Exactamente un dígito en cualquier posición: 3 posiciones · 2² letras = 12 contraseñas.
+    let config = config(
+        r#"
+[rules]
+length = 3
+digits = 1
+
+[charset]
+letters = "ab"
+digits = "1"
+"#,
+    );
+    let mut generator = GenPassword::seed_from_u64(config, 3);
+
+    let mut frequencies: HashMap<String, u32> = HashMap::new();
+    for _ in 0..12_000 {
+        *frequencies.entry(generator.generate().unwrap().password).or_default() += 1;
+    }
+
+    assert_eq!(frequencies.len(), 12);
+    for (password, frequency) in frequencies {
+        assert_eq!(password.matches('1').count(), 1, "{}", password);
+        assert!(
+            (850..=1150).contains(&frequency),
+            "{} appeared {} times",
+            password,
+            frequency
+        );
+    }
+}
+
+/// Indica si `password` contiene `max + 1` dígitos seguidos consecutivos, ascendentes o
+/// descendentes. Con los dígitos `0-4` de la distribución de EE. UU. los recorridos de teclado
+/// coinciden con las secuencias numéricas.
+fn has_sequence(password: &[i32], max: usize) -> bool {
+    password.windows(max + 1).any(|w| {
+        let step = w[1] - w[0];
+        step.abs() == 1 && w.windows(2).all(|pair| pair[1] - pair[0] == step)
+    })
+}
+
+#[test]
+fn max_sequence_count_matches_brute_force() {
+    for max in 1..=3 {
+        let config = config(&format!(
+            r#"
+[rules]
+length = 5
+max-sequence = {}
+
+[charset]
+digits = "01234"
+"#,
+            max
+        ));
+
+        let mut expected = 0u32;
+        for n in 0..5i32.pow(5) {
+            let password: Vec<i32> = (0..5).map(|i| n / 5i32.pow(i) % 5).collect();
+            if !has_sequence(&password, max) {
+                expected += 1;
+            }
+        }
+
+        assert_eq!(
+            config.entropy().unwrap().count,
+            expected.into(),
+            "max-sequence = {}",
+            max
+        );
+    }
+}
+
+#[test]
+fn mixed_rules_count_matches_brute_force() {
+    // Secuencias, repeticiones y contadores por charset a la vez.
+    let config = config(
+        r#"
+[rules]
+length = 5
+max-sequence = 2
+max-consecutive = 2
+odd = { min = 1, max = 2 }
+
+[charset]
+digits = "01234"
+odd = "13"
+"#,
+    );
+
+    let mut expected = 0u32;
+    for n in 0..5i32.pow(5) {
+        let password: Vec<i32> = (0..5).map(|i| n / 5i32.pow(i) % 5).collect();
+        let odd = password.iter().filter(|&&c| c % 2 == 1).count();
+        let repeated = password.windows(3).any(|w| w[0] == w[1] && w[1] == w[2]);
+        if !has_sequence(&password, 2) && !repeated && (1..=2).contains(&odd) {
+            expected += 1;
+        }
+    }
+
+    assert_eq!(config.entropy().unwrap().count, expected.into());
+}
+
+#[test]
+fn max_sequence_samples_uniformly() {
+    // Con "012" y longitud 3 solo se prohíben "012" y "210": quedan 27 - 2 = 25 contraseñas.
+    let config = config(
+        r#"
+[rules]
+length = 3
+max-sequence = 2
+
+[charset]
+digits = "012"
+"#,
+    );
+    let mut generator = GenPassword::seed_from_u64(config, 5);
+
+    let mut frequencies: HashMap<String, u32> = HashMap::new();
+    for _ in 0..25_000 {
+        *frequencies.entry(generator.generate().unwrap().password).or_default() += 1;
+    }
+
+    assert_eq!(frequencies.len(), 25);
+    for (password, frequency) in frequencies {
+        assert!(password != "012" && password != "210", "{}", password);
+        assert!(
+            (850..=1150).contains(&frequency),
+            "{} appeared {} times",
+            password,
+            frequency
+        );
+    }
+}
+
+#[test]
+fn mixed_rules_are_counted_once() {
+    // Recuento costoso: varios charsets con mínimo y máximo, secuencias, repeticiones y una
+    // restricción de posición. La validación lo hace una sola vez y `generate` lo reutiliza, así
+    // que muchas generaciones no repiten el coste de la carga.
+    let config = config(
+        r#"
+[rules]
+length = { min = 10, max = 14 }
+max-sequence = 3
+max-consecutive = 2
+upper = { min = 1 }
+digits = { min = 2, max = 10 }
+symbols = { min = 2, max = 10 }
+first = '@ascii_letters'
+
+[charset]
+all = "@printable"
+upper = "A-Z"
+digits = "0-9"
+symbols = "@punctuation"
+"#,
+    );
+    let mut generator = GenPassword::seed_from_u64(config, 3);
+
+    for _ in 0..200 {
+        let password: Vec<char> = generator.generate().unwrap().password.chars().collect();
+        let count = |f: fn(&char) -> bool| password.iter().filter(|c| f(c)).count();
+
+        assert!((10..=14).contains(&password.len()), "{:?}", password);
+        assert!(password[0].is_ascii_alphabetic(), "{:?}", password);
+        assert!(count(char::is_ascii_uppercase) >= 1, "{:?}", password);
+        assert!((2..=10).contains(&count(char::is_ascii_digit)), "{:?}", password);
+        assert!((2..=10).contains(&count(char::is_ascii_punctuation)), "{:?}", password);
+        assert!(
+            password.windows(3).all(|w| w[0] != w[1] || w[1] != w[2]),
+            "{:?}",
+            password
+        );
+    }
+}
+
+/// Política habitual de 12 a 64 caracteres con primer y último carácter, mínimos y máximos por
+/// charset y `max-sequence`, hasta la longitud `max`.
+fn realistic_policy(max: usize) -> String {
+    format!(
+        r#"
+[rules]
+length = {{ min = 12, max = {} }}
+max-sequence = 3
+upper = {{ min = 1 }}
+digits = {{ min = 2, max = 10 }}
+symbols = {{ min = 2, max = 10 }}
+first = '@ascii_letters'
+last = '@digits'
+
+[charset]
+all = "@printable"
+upper = "A-Z"
+digits = "0-9"
+symbols = "@punctuation"
+"#,
+        max
+    )
+}
+
+#[test]
+fn realistic_policies_load_quickly() {
+    // Los contadores por charset no deben multiplicar los estados de `max-sequence`: antes esta
+    // carga tardaba más de medio minuto en release. El margen cubre las pruebas sin optimizar.
+    let start = Instant::now();
+    let config = config(&realistic_policy(64));
+    let password = GenPassword::seed_from_u64(config, 5).generate().unwrap().password;
+    assert!((12..=64).contains(&password.chars().count()), "{}", password);
+    assert!(
+        start.elapsed() < Duration::from_secs(30),
+        "loading took {:?}",
+        start.elapsed()
+    );
+}
+
+#[test]
+fn overly_complex_rules_fail_fast() {
+    // El límite se comprueba al recorrer los estados, antes de contar nada.
+    let start = Instant::now();
+    let message = config_message(&realistic_policy(256));
+    assert!(message.contains("too complex to count exactly"), "{}", message);
+    assert!(
+        start.elapsed() < Duration::from_secs(5),
+        "rejecting took {:?}",
+        start.elapsed()
+    );
+}
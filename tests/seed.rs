@@ -0,0 +1,64 @@
+mod common;
+
+use common::config;
+use kryptopass_utils::password_generator::{config::Config, GenPassword};
+
+fn golden_config() -> Config {
+    config(
+        r#"
+[rules]
+length = { min = 12, max = 16 }
+pattern = '"KP-"(uppercase)*'
+max-consecutive = 2
+digits = { min = 2, max = 4 }
+symbols = { min = 1 }
+
+[charset]
+uppercase = "A-Z"
+lowercase = "a-z"
+digits = "0-9"
+symbols = "!#$%&*+-=?@"
+"#,
+    )
+}
+
+/// Resultados de referencia: cambiar el algoritmo de generación o el consumo del generador
+/// aleatorio cambia estas contraseñas, y eso rompe la promesa de reproducibilidad de
+/// `GenPassword::from_seed` (ver "Fuente de aleatoriedad" en PASSWORD.md).
+#[test]
+fn golden_passwords_for_a_fixed_seed() {
+    let mut generator = GenPassword::seed_from_u64(golden_config(), 42);
+    let passwords: Vec<String> = (0..4).map(|_| generator.generate().unwrap().password).collect();
+    assert_eq!(
+        passwords,
+        [
+            "KP-OKZ2in8BPSTTU",
+            "KP-Sv4@#OE4&1MQY",
+            "KP-W?UL!Ga+W1R47",
+            "KP-AFg6YaRneh1Pv"
+        ]
+    );
+
+    let mut generator = GenPassword::from_seed(golden_config(), [7; 32]);
+    let passwords: Vec<String> = (0..2).map(|_| generator.generate().unwrap().password).collect();
+    assert_eq!(passwords, ["KP-NeOdR2xCJk$o7", "KP-ZU6*4pSGLg50Z"]);
+}
+
+#[test]
+fn same_seed_gives_the_same_sequence() {
+    let mut first = GenPassword::seed_from_u64(golden_config(), 1);
+    let mut second = GenPassword::seed_from_u64(golden_config(), 1);
+    for _ in 0..20 {
+        let (first, second) = (first.generate().unwrap(), second.generate().unwrap());
+        assert_eq!((first.password, first.length), (second.password, second.length));
+    }
+}
+
+#[test]
+fn different_seeds_give_different_sequences() {
+    let mut first = GenPassword::seed_from_u64(golden_config(), 1);
+    let mut second = GenPassword::seed_from_u64(golden_config(), 2);
+    let first: Vec<String> = (0..5).map(|_| first.generate().unwrap().password).collect();
+    let second: Vec<String> = (0..5).map(|_| second.generate().unwrap().password).collect();
+    assert_ne!(first, second);
+}
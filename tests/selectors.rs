@@ -0,0 +1,53 @@
+mod common;
+
+use common::{alphabet, config_message};
+
+/// Configuración con un único charset `value`.
+fn single(value: &str) -> String {
+    format!("[rules]\nlength = 8\n\n[charset]\nchars = {}\n", value)
+}
+
+#[test]
+fn general_categories_select_assigned_characters() {
+    assert_eq!(alphabet(&single(r"'\p{Lu} & [a-zA-F0-9]'")), "ABCDEF");
+    assert_eq!(alphabet(&single(r"'\p{gc=Nd} & [0-9a-f]'")), "0123456789");
+}
+
+#[test]
+fn scripts_select_assigned_characters() {
+    // U+03A2 está sin asignar dentro del rango de las mayúsculas griegas.
+    let capitals = "ΑΒΓΔΕΖΗΘΙΚΛΜΝΞΟΠΡΣΤΥΦΧΨΩ";
+    assert_eq!(alphabet(&single(r"'\p{Script=Greek} & [U+0391-U+03A9]'")), capitals);
+    assert_eq!(alphabet(&single(r"'\p{sc=Grek} & [U+0391-U+03A9]'")), capitals);
+    assert_eq!(alphabet(&single(r"'\p{Greek} & \p{Lu} & [U+0391-U+03A9]'")), capitals);
+}
+
+#[test]
+fn negated_properties_select_the_rest() {
+    assert_eq!(alphabet(&single(r"'\P{Lu} & [a-cA-C0-2]'")), "012abc");
+    assert_eq!(alphabet(&single(r"'[a-cα-γ] & \P{Script=Latin}'")), "αβγ");
+}
+
+#[test]
+fn blocks_select_their_assigned_characters() {
+    assert_eq!(alphabet(&single("'block:Hiragana & [ぁ-ぅ]'")), "ぁあぃいぅ");
+    assert_eq!(alphabet(&single("'block:hiragana & [ぁ-ぅ]'")), "ぁあぃいぅ");
+    assert_eq!(alphabet(&single("'block:Greek and Coptic & [U+0378-U+037B]'")), "ͺͻ");
+}
+
+#[test]
+fn unknown_properties_and_blocks_are_rejected() {
+    assert_eq!(
+        config_message(&single(r"'\p{Script=Klingon}'")),
+        "Invalid configuration: Error procesando '\\p{Script=Klingon}' en charset 'chars': \
+         Propiedad Unicode desconocida: \\p{Script=Klingon}"
+    );
+    assert_eq!(
+        config_message(&single(r"'\P{Foo}'")),
+        "Invalid configuration: Error procesando '\\P{Foo}' en charset 'chars': Propiedad Unicode desconocida: \\P{Foo}"
+    );
+    assert_eq!(
+        config_message(&single("'block:Nope'")),
+        "Invalid configuration: Error procesando 'block:Nope' en charset 'chars': Bloque Unicode desconocido: Nope"
+    );
+}
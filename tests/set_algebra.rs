@@ -0,0 +1,88 @@
+mod common;
+
+use common::{alphabet, config_message};
+
+/// Configuración con un único charset `value`.
+fn single(value: &str) -> String {
+    format!("[rules]\nlength = 8\n\n[charset]\nchars = {}\n", value)
+}
+
+#[test]
+fn operators_combine_sets_from_left_to_right() {
+    assert_eq!(alphabet(&single("'a-f - aeiou'")), "bcdf");
+    assert_eq!(alphabet(&single("'a-f & c-z'")), "cdef");
+    assert_eq!(alphabet(&single("'a-c + x-z'")), "abcxyz");
+    // (hexdigits ∩ a-z) ∪ A-B
+    assert_eq!(alphabet(&single("'@hexdigits & a-z + A-B'")), "ABabcdef");
+    // (a-f ∪ 0-3) − 2-9
+    assert_eq!(alphabet(&single("'a-f + 0-3 - 2-9'")), "01abcdef");
+}
+
+#[test]
+fn references_can_be_operands() {
+    let body = r#"
+[rules]
+length = 8
+
+[charset]
+vowels = "aeiou"
+consonants = "@ascii_lowercase - @vowels"
+"#;
+    assert_eq!(alphabet(body), "abcdefghijklmnopqrstuvwxyz");
+    let config = common::config(body);
+    assert_eq!(
+        config.charset.get("consonants").unwrap().iter().collect::<String>(),
+        "bcdfghjklmnpqrstvwxyz"
+    );
+}
+
+#[test]
+fn expressions_work_in_include_and_exclude() {
+    let body = r#"
+[rules]
+length = 8
+exclude = ["a-z & @hexdigits"]
+include = ["0-9 - 1-8"]
+
+[charset]
+lower = "a-h"
+"#;
+    assert_eq!(alphabet(body), "09gh");
+}
+
+#[test]
+fn operators_need_surrounding_spaces() {
+    assert_eq!(alphabet(&single("'a-c'")), "abc");
+    assert_eq!(alphabet(&single("'ab+cd'")), "+abcd");
+    assert_eq!(alphabet(&single("'hola mundo'")), " adhlmnou");
+}
+
+#[test]
+fn escaped_operators_are_characters() {
+    assert_eq!(alphabet(&single(r"'0-2 + \-'")), "-012");
+    assert_eq!(alphabet(&single(r"'0-2 + \+ + \&'")), "&+012");
+    assert_eq!(alphabet(&single(r"'x \- y'")), " -xy");
+    assert_eq!(alphabet(&single(r"'a \+ b \& c'")), " &+abc");
+}
+
+#[test]
+fn single_character_operands_are_ambiguous() {
+    for value in ["'x - y'", "'a + b'", "'1 & 2'", "'a + b - c'"] {
+        let message = config_message(&single(value));
+        assert!(
+            message.starts_with(&format!(
+                "Invalid configuration: Expresión de conjuntos inválida '{}': todos los operandos son caracteres sueltos",
+                value.trim_matches('\'')
+            )),
+            "{}: {}",
+            value,
+            message
+        );
+    }
+}
+
+#[test]
+fn single_characters_are_fine_next_to_sets() {
+    assert_eq!(alphabet(&single("'0-2 + _'")), "012_");
+    assert_eq!(alphabet(&single("'@digits - 5'")), "012346789");
+}
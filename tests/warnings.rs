@@ -0,0 +1,124 @@
+mod common;
+
+use common::config;
+use kryptopass_utils::password_generator::config::ConfigWarning;
+
+#[test]
+fn valid_configuration_has_no_warnings() {
+    let config = config(
+        r#"
+[rules]
+length = 12
+
+[charset]
+lowercase = "a-z"
+"#,
+    );
+    assert!(config.warnings().is_empty());
+}
+
+#[test]
+fn removed_characters_are_reported_with_a_hint() {
+    let config = config(
+        r#"
+[rules]
+length = 12
+
+[charset]
+lowercase = "a-z"
+latin1 = { chars = ["U+0000-U+0002", "xyz"] }
+"#,
+    );
+    assert_eq!(
+        config.warnings(),
+        [ConfigWarning::Removed {
+            source: "charset 'latin1'".into(),
+            description: "control characters".into(),
+            elements: vec!["\u{0}".into(), "\u{1}".into(), "\u{2}".into()],
+            hint: Some("add \"control\" to allow in charset 'latin1' to keep them".into()),
+        }]
+    );
+    assert_eq!(
+        config.warnings()[0].to_string(),
+        "Removed 3 control characters from charset 'latin1': U+0000, U+0001, U+0002 \
+         (add \"control\" to allow in charset 'latin1' to keep them)."
+    );
+}
+
+#[test]
+fn long_lists_are_shortened() {
+    let config = config(
+        r#"
+[rules]
+length = 12
+
+[charset]
+lowercase = "a-z"
+controls = { chars = ["U+0000-U+001F", "xyz"] }
+"#,
+    );
+    let message = config.warnings()[0].to_string();
+    assert!(message.starts_with("Removed 32 control characters from charset 'controls': U+0000, U+0001,"));
+    assert!(message.contains("U+0007, ... (add"));
+}
+
+#[test]
+fn include_and_exclude_warnings_are_collected_in_order() {
+    let config = config(
+        r#"
+[rules]
+length = 12
+exclude = ["0-9"]
+include = ["5"]
+avoid-ambiguous = true
+
+[charset]
+lowercase = "a-z"
+digits = "0-9"
+"#,
+    );
+    assert_eq!(
+        config.warnings(),
+        [
+            ConfigWarning::Reintroduced("5".into()),
+            ConfigWarning::AmbiguousIncluded("5".into()),
+        ]
+    );
+}
+
+#[test]
+fn untypeable_includes_are_reported() {
+    let config = config(
+        r#"
+[rules]
+length = 12
+include = ["ñ"]
+keyboard = ["us"]
+
+[charset]
+lowercase = "a-z"
+"#,
+    );
+    assert_eq!(config.warnings(), [ConfigWarning::Untypeable("ñ".into())]);
+}
+
+#[test]
+fn unused_language_sections_are_reported() {
+    let config = config(
+        r#"
+[rules]
+length = 12
+
+[charset]
+lowercase = "a-z"
+
+[de]
+umlauts = "äöü"
+"#,
+    );
+    assert_eq!(config.warnings(), [ConfigWarning::IgnoredLanguage("de".into())]);
+    assert_eq!(
+        config.warnings()[0].to_string(),
+        "Section [de] is ignored because 'de' is not listed in profile.lang."
+    );
+}